@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "pdf" (
+       "plain"          TEXT NOT NULL,
+       "pages"          TEXT NOT NULL DEFAULT '[]',
+       "document"       INTEGER NOT NULL,
+       FOREIGN KEY("document") REFERENCES "documents"("id") ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS "image" (
+       "plain"          TEXT NOT NULL,
+       "mime"           TEXT NOT NULL,
+       "data"           BLOB NOT NULL,
+       "document"       INTEGER NOT NULL,
+       FOREIGN KEY("document") REFERENCES "documents"("id") ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "text" (
+       "plain"          TEXT NOT NULL,
+       "rich"           TEXT NULL,
+       "document"       INTEGER NOT NULL,
+       FOREIGN KEY("document") REFERENCES "documents"("id") ON DELETE CASCADE
+);
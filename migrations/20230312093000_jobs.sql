@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS "jobs" (
+       "id"             INTEGER NOT NULL,
+       "url"            TEXT NOT NULL,
+       "job"            TEXT NOT NULL,
+       "state"          TEXT NOT NULL DEFAULT 'queued',
+       "attempts"       INTEGER NOT NULL DEFAULT 0,
+       "created"        TEXT NOT NULL,
+       "updated"        TEXT NOT NULL,
+       "not_before"     INTEGER NOT NULL DEFAULT 0,
+       "outcome"        TEXT NULL,
+       "document"       BLOB NULL,
+       PRIMARY KEY("id" AUTOINCREMENT)
+);
@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS "feeds" (
+       "id"             INTEGER NOT NULL,
+       "url"            TEXT NOT NULL UNIQUE,
+       "title"          TEXT NULL,
+       "tags"           TEXT NOT NULL DEFAULT '[]',
+       "added"          TEXT NOT NULL,
+       "last_polled"    TEXT NULL,
+       PRIMARY KEY("id" AUTOINCREMENT)
+);
+
+CREATE TABLE IF NOT EXISTS "feed_entries" (
+       "feed"           INTEGER NOT NULL,
+       "guid"           TEXT NOT NULL,
+       "url"            TEXT NOT NULL,
+       "ingested"       TEXT NOT NULL,
+       UNIQUE("feed", "guid"),
+       FOREIGN KEY("feed") REFERENCES "feeds"("id") ON DELETE CASCADE
+);
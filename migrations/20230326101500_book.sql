@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS "book" (
+       "plain"          TEXT NOT NULL,
+       "chapters"       TEXT NOT NULL DEFAULT '[]',
+       "document"       INTEGER NOT NULL,
+       FOREIGN KEY("document") REFERENCES "documents"("id") ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "redirects" (
+       "url"            TEXT NOT NULL,
+       "position"       INTEGER NOT NULL,
+       "document"       INTEGER NOT NULL,
+       FOREIGN KEY("document") REFERENCES "documents"("id") ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS "redirects_url" ON "redirects"("url");
@@ -0,0 +1 @@
+ALTER TABLE "jobs" ADD COLUMN "heartbeat" INTEGER NOT NULL DEFAULT 0;
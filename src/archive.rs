@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 use chrono::{DateTime, Local, Utc};
 use futures::StreamExt;
+use isahc::http::HeaderMap;
 use miette::Diagnostic;
 use serde::Deserialize;
 use serde_json::{json, Value};
@@ -14,8 +15,10 @@ use tokio::io::AsyncWriteExt;
 // use tokio_stream::StreamExt;
 use tokio_stream::wrappers::ReadDirStream;
 
+pub use crate::export::warc::{export_warc, ExportError, Exported};
 use crate::job::JobError;
-use crate::source::{Page, Source};
+use crate::source::video::Video;
+use crate::source::{Email, Epub, Image, Notebook, Page, Pdf, Source, Text};
 use crate::url_preferences::{self, UrlPreferences};
 use crate::Seen;
 
@@ -80,39 +83,91 @@ pub async fn recover(seen: &Seen) -> Result<(), RecoverError> {
 pub async fn recover_source(seen: &Seen, file: impl AsRef<Path>) -> Result<(), RecoverError> {
     let archived = serde_json::from_str::<Archived>(&read_to_string(file).await?)?;
 
-    match archived.source {
-        ArchivedSource::Page(page) => {
-            let url_preferences: Option<UrlPreferences> =
-                url_preferences::for_url(&page.url, seen).await;
+    let source = match archived.source {
+        ArchivedSource::Email(email) => Source::Email(email),
+        ArchivedSource::Epub(epub) => Source::Epub(epub),
+        ArchivedSource::Image(image) => Source::Image(image),
+        ArchivedSource::Notebook(notebook) => Source::Notebook(notebook),
+        ArchivedSource::Page(page) => Source::Page(page),
+        ArchivedSource::Pdf(pdf) => Source::Pdf(pdf),
+        ArchivedSource::Text(text) => Source::Text(text),
+        ArchivedSource::Video(video) => Source::Video(video),
+    };
+
+    let url = source.url().unwrap().clone();
+
+    let url_preferences: Option<UrlPreferences> = url_preferences::for_url(&url, seen).await;
+
+    let preferences = match url_preferences {
+        Some(UrlPreferences::Blacklist) => Err(JobError::Blacklisted),
+        Some(UrlPreferences::Preferences(s)) => Ok(s),
+        None => Ok(Default::default()),
+    }
+    .unwrap();
+
+    let tags = archived
+        .metadata
+        .get("tag")
+        .and_then(|t| serde_json::from_value::<Vec<String>>(t.clone()).ok())
+        .unwrap_or_default();
+
+    crate::job::index_source(
+        seen,
+        &url,
+        &[],
+        source,
+        &preferences,
+        archived.metadata,
+        archived.time,
+        &tags,
+    )
+    .await
+    .unwrap();
+
+    Ok(())
+}
+
+/// HTTP headers of the most recently archived source of each of `urls`
+/// (those which were archived). Archive files are read from the newest
+/// until all of `urls` are found, files which cannot be read are ignored.
+pub(crate) async fn latest_headers(
+    seen: &Seen,
+    urls: &HashSet<String>,
+) -> HashMap<String, HeaderMap> {
+    let mut files = vec![];
+
+    if let Ok(mut rd) = read_dir(seen.archive_dir()).await {
+        while let Ok(Some(entry)) = rd.next_entry().await {
+            files.push(entry.path());
+        }
+    }
+
+    // Files are named by time of archival.
+    files.sort();
+
+    let mut headers = HashMap::new();
 
-            let preferences = match url_preferences {
-                Some(UrlPreferences::Blacklist) => Err(JobError::Blacklisted),
-                Some(UrlPreferences::Preferences(s)) => Ok(s),
-                None => Ok(Default::default()),
+    for file in files.into_iter().rev() {
+        if headers.len() == urls.len() {
+            break;
+        }
+
+        // Only files mentioning one of the URLs are worth decoding.
+        let archived = match read_to_string(&file).await {
+            Ok(s) if urls.iter().any(|u| s.contains(u.as_str())) => {
+                serde_json::from_str::<Archived>(&s).ok()
+            }
+            _ => None,
+        };
+
+        if let Some((url, h)) = archived.and_then(|a| a.source.into_headers()) {
+            if urls.contains(&url) {
+                headers.entry(url).or_insert(h);
             }
-            .unwrap();
-
-            let tags = archived
-                .metadata
-                .get("tag")
-                .and_then(|t| serde_json::from_value::<Vec<String>>(t.clone()).ok())
-                .unwrap_or_default();
-
-            crate::job::index_source(
-                seen,
-                &page.url.clone(),
-                Source::Page(page),
-                &preferences,
-                archived.metadata,
-                archived.time,
-                &tags,
-            )
-            .await
-            .unwrap()
         }
     }
 
-    Ok(())
+    headers
 }
 
 /// Archived source with all the available metadata. The metadata includes
@@ -127,5 +182,28 @@ pub struct Archived {
 /// Archived source.
 #[derive(Debug, Clone, Deserialize)]
 pub enum ArchivedSource {
+    Email(Email),
+    Epub(Epub),
+    Image(Image),
+    Notebook(Notebook),
     Page(Page),
+    Pdf(Pdf),
+    Text(Text),
+    Video(Video),
+}
+
+impl ArchivedSource {
+    /// URL of the source and HTTP headers with which it was returned, if they were kept.
+    fn into_headers(self) -> Option<(String, HeaderMap)> {
+        match self {
+            ArchivedSource::Epub(e) => Some((e.url.to_string(), e.headers)),
+            ArchivedSource::Image(i) => Some((i.url.to_string(), i.headers)),
+            ArchivedSource::Page(p) => Some((p.url.to_string(), p.headers)),
+            ArchivedSource::Pdf(p) => Some((p.url.to_string(), p.headers)),
+            ArchivedSource::Text(t) => Some((t.url.to_string(), t.headers)),
+            ArchivedSource::Email(_) | ArchivedSource::Notebook(_) | ArchivedSource::Video(_) => {
+                None
+            }
+        }
+    }
 }
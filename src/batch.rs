@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+use std::error::Error;
+
+use chrono::{DateTime, Local};
+use futures::StreamExt;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use uuid::Uuid;
+
+use crate::job::{self, Job, JobError, SourceHints};
+use crate::local::target_uri;
+use crate::{queue, Seen};
+
+/// One line of a URL list.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    /// URL or local file, as written in the list.
+    pub target: String,
+    /// Tags of this URL only.
+    pub tags: Vec<String>,
+    /// When the URL was seen, if known.
+    pub time: Option<DateTime<Local>>,
+}
+
+/// What happened to an entry of the list.
+#[derive(Debug)]
+pub enum Status {
+    /// The source was indexed as document with the UUID (none in case of dry run).
+    Added(Option<Uuid>),
+    /// The document was downloaded again and re-indexed as document with the UUID
+    /// (none in case of dry run).
+    Updated(Option<Uuid>),
+    /// The source was queued as job with the ID.
+    Queued(i64),
+    /// The source was not even downloaded, for the given reason.
+    Skipped(String),
+    /// Processing of the source failed.
+    Failed(String),
+}
+
+/// Result of processing one entry of the list.
+#[derive(Debug)]
+pub struct Report {
+    pub target: String,
+    pub status: Status,
+}
+
+/// Read list of URLs, one per line, each optionally followed by tags separated
+/// by whitespace. Empty lines and lines starting with `#` are ignored.
+pub fn parse_list(list: &str) -> Vec<Entry> {
+    list.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            let mut words = l.split_whitespace();
+            words.next().map(|target| Entry {
+                target: target.to_string(),
+                tags: words.map(|t| t.to_string()).collect(),
+                time: None,
+            })
+        })
+        .collect()
+}
+
+/// Add all sources in `entries`, at most `concurrency` at once. Sources already
+/// indexed or repeated in the list are skipped. Failures do not stop processing
+/// of the remaining entries. Reports are returned in the order of `entries`.
+pub async fn add_all(
+    seen: &Seen,
+    entries: Vec<Entry>,
+    tags: &[String],
+    concurrency: usize,
+    archive: bool,
+    dry_run: bool,
+) -> Vec<Report> {
+    let pb = if seen.options.show_progress_bar {
+        ProgressBar::new(entries.len() as u64)
+    } else {
+        ProgressBar::hidden()
+    };
+    pb.set_style(
+        ProgressStyle::with_template("{bar:40.green/yellow} {pos:>7}/{len:7} {wide_msg}").unwrap(),
+    );
+
+    let mut seen_urls = HashSet::new();
+
+    let mut reports = futures::stream::iter(entries.into_iter().enumerate())
+        .map(|(i, entry)| {
+            // URL is resolved here, synchronously, so that repeated URLs
+            // are recognized before any of them is processed.
+            let url = target_uri(&entry.target);
+            let repeated = matches!(&url, Ok(url) if !seen_urls.insert(url.to_string()));
+            let pb = &pb;
+
+            async move {
+                let status = match url {
+                    Err(e) => Status::Failed(describe(&e)),
+                    Ok(_) if repeated => Status::Skipped("repeated in list".to_string()),
+                    Ok(url) => {
+                        pb.set_message(url.to_string());
+                        let job = Job {
+                            url,
+                            tags: [tags, entry.tags.as_slice()].concat(),
+                            hints: SourceHints::default(),
+                            archive,
+                            time: entry.time,
+                        };
+                        add_one(seen, job, dry_run).await
+                    }
+                };
+                pb.inc(1);
+
+                (
+                    i,
+                    Report {
+                        target: entry.target,
+                        status,
+                    },
+                )
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    pb.finish_and_clear();
+
+    reports.sort_by_key(|(i, _)| *i);
+    reports.into_iter().map(|(_, r)| r).collect()
+}
+
+async fn add_one(seen: &Seen, job: Job, dry_run: bool) -> Status {
+    match job::existing_document(seen, &job.url).await {
+        Ok(Some(_)) => return Status::Skipped("already indexed".to_string()),
+        Ok(None) => (),
+        Err(e) => return Status::Failed(describe(&e)),
+    }
+
+    // Progress of individual sources would only clutter the overall progress bar.
+    let multi = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+
+    match job::add(seen, job, dry_run, &multi).await {
+        Ok(uuid) => Status::Added(uuid),
+        Err(JobError::Blacklisted) => Status::Skipped("blacklisted".to_string()),
+        Err(JobError::DisallowedByRobots(_)) => {
+            Status::Skipped("disallowed by robots.txt".to_string())
+        }
+        Err(e) => Status::Failed(describe(&e)),
+    }
+}
+
+/// Queue all sources in `entries` to be added later by a worker. Sources
+/// already indexed or repeated in the list are skipped.
+pub async fn enqueue_all(
+    seen: &Seen,
+    entries: Vec<Entry>,
+    tags: &[String],
+    archive: bool,
+) -> Vec<Report> {
+    let mut seen_urls = HashSet::new();
+    let mut reports = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let status = match target_uri(&entry.target) {
+            Err(e) => Status::Failed(describe(&e)),
+            Ok(url) if !seen_urls.insert(url.to_string()) => {
+                Status::Skipped("repeated in list".to_string())
+            }
+            Ok(url) => match job::existing_document(seen, &url).await {
+                Ok(Some(_)) => Status::Skipped("already indexed".to_string()),
+                Err(e) => Status::Failed(describe(&e)),
+                Ok(None) => {
+                    let job = Job {
+                        url,
+                        tags: [tags, entry.tags.as_slice()].concat(),
+                        hints: SourceHints::default(),
+                        archive,
+                        time: entry.time,
+                    };
+                    match queue::enqueue(seen, &job).await {
+                        Ok(id) => Status::Queued(id),
+                        Err(e) => Status::Failed(describe(&e)),
+                    }
+                }
+            },
+        };
+
+        reports.push(Report {
+            target: entry.target,
+            status,
+        });
+    }
+
+    reports
+}
+
+/// Error message including its causes, for reports.
+pub(crate) fn describe(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(e) = source {
+        message.push_str(&format!(" {e}"));
+        source = e.source();
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_list, Entry};
+
+    #[test]
+    fn parse_url_list() {
+        let list = "# reading list\n\nhttps://example.com/a rust async\n  https://example.com/b\n";
+
+        assert_eq!(
+            parse_list(list),
+            vec![
+                Entry {
+                    target: "https://example.com/a".to_string(),
+                    tags: vec!["rust".to_string(), "async".to_string()],
+                    time: None,
+                },
+                Entry {
+                    target: "https://example.com/b".to_string(),
+                    tags: vec![],
+                    time: None,
+                },
+            ]
+        );
+    }
+}
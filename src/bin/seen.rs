@@ -1,11 +1,18 @@
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 
 use clap::{Parser, Subcommand};
 use comfy_table::{presets, Attribute, Cell, CellAlignment, Table};
 use futures::StreamExt;
 use isahc::http::Uri;
-use miette::Result;
-use seen::document::Content;
+use miette::{IntoDiagnostic, Result};
+use seen::batch::{Entry, Report, Status};
+use seen::crawl::CrawlOptions;
+use seen::document::{Content, Location};
+use seen::import::history::HistoryFilter;
+use seen::job::{format_timestamp, Job, SourceHints, TranscriptLocation};
+use seen::local::target_uri;
+use seen::refresh::{parse_age, Selection};
 use seen::Seen;
 use uuid::Uuid;
 
@@ -17,12 +24,76 @@ async fn main() -> Result<()> {
 
     match args.command {
         Command::Add(Add {
+            from_file: Some(file),
+            jobs,
+            tags,
+            no_archive,
+            dry_run,
+            background,
+            ..
+        }) => {
+            let list = String::from_utf8_lossy(&read_input(&file)?).into_owned();
+            let entries = seen::batch::parse_list(&list);
+
+            let batch = Batch {
+                tags,
+                jobs,
+                no_archive,
+                dry_run,
+                background,
+            };
+            add_entries(&seen, entries, batch).await;
+        }
+        Command::Add(Add {
+            target,
             url,
+            stdin,
             tags,
+            transcript,
+            referrer,
             no_archive,
             dry_run,
+            background,
+            ..
         }) => {
-            seen::job::go(&seen, url, &tags, !no_archive, dry_run).await?;
+            let html = if stdin {
+                let mut html = Vec::new();
+                std::io::stdin().read_to_end(&mut html).into_diagnostic()?;
+                Some(html)
+            } else {
+                None
+            };
+            let hints = SourceHints {
+                transcript,
+                referrer,
+                html,
+                ..Default::default()
+            };
+            let url = target.or(url).expect("Clap requires either target or URL.");
+
+            if background && !seen.always_in_foreground() {
+                // Worker may run in another directory.
+                let hints = match hints.transcript {
+                    Some(TranscriptLocation::File(path)) => SourceHints {
+                        transcript: Some(TranscriptLocation::File(
+                            path.canonicalize().into_diagnostic()?,
+                        )),
+                        ..hints
+                    },
+                    _ => hints,
+                };
+                let job = Job {
+                    url,
+                    tags,
+                    hints,
+                    archive: !no_archive,
+                    time: None,
+                };
+                let id = seen::queue::enqueue(&seen, &job).await?;
+                println!("Queued as job {id}.");
+            } else {
+                seen::job::go(&seen, url, &tags, hints, !no_archive, dry_run).await?;
+            }
         }
         Command::Delete(Delete { uuid }) => {
             seen.delete(&uuid).await?;
@@ -38,6 +109,56 @@ async fn main() -> Result<()> {
                             println!("{}\n\n{}", doc.title, text);
                         }
                     }
+                    Content::Pdf { pages, .. } => {
+                        let content = pages
+                            .iter()
+                            .enumerate()
+                            .map(|(i, page)| format!("## Page {}\n\n{}", i + 1, page))
+                            .collect::<Vec<_>>()
+                            .join("\n\n");
+                        let content = format!("# {}\n\n{}", doc.title, content);
+                        display_content(&content).unwrap();
+                    }
+                    Content::Book { chapters, .. } => {
+                        let content = chapters
+                            .iter()
+                            .enumerate()
+                            .map(|(i, chapter)| match &chapter.title {
+                                Some(title) => format!("## {title}\n\n{}", chapter.text),
+                                None => format!("## Chapter {}\n\n{}", i + 1, chapter.text),
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n\n");
+                        let content = format!("# {}\n\n{}", doc.title, content);
+                        display_content(&content).unwrap();
+                    }
+                    Content::Image { text, mime, data } => {
+                        println!(
+                            "{}\n\n{} ({} bytes)\n\n{}",
+                            doc.title,
+                            mime,
+                            data.len(),
+                            text
+                        );
+                    }
+                    Content::Text { text, rich_text } => {
+                        // Markdown already starts with its title, plain text
+                        // is best displayed as it is.
+                        let content = match rich_text {
+                            Some(markdown) => markdown,
+                            None => format!("# {}\n\n```\n{}\n```", doc.title, text),
+                        };
+                        display_content(&content).unwrap();
+                    }
+                    Content::Video { cues, .. } => {
+                        let content = cues
+                            .iter()
+                            .map(|cue| format!("`{}` {}", format_timestamp(cue.start), cue.text))
+                            .collect::<Vec<_>>()
+                            .join("\n\n");
+                        let content = format!("# {}\n\n{}", doc.title, content);
+                        display_content(&content).unwrap();
+                    }
                 };
             } else {
                 println!("Not found.");
@@ -108,6 +229,29 @@ async fn main() -> Result<()> {
                         ]);
                     }
 
+                    if let Some(location) = document.content.locate(&hit.fragment) {
+                        let location = match location {
+                            Location::Page(page) => format!("page {page}"),
+                            Location::Chapter(chapter, Some(title)) => {
+                                format!("chapter {chapter} ({title})")
+                            }
+                            Location::Chapter(chapter, None) => format!("chapter {chapter}"),
+                            Location::Time(millis) => format!(
+                                "{} ({}#t={})",
+                                format_timestamp(millis),
+                                document.url,
+                                millis / 1000
+                            ),
+                        };
+
+                        table.add_row(vec![
+                            Cell::new("Found at")
+                                .add_attribute(Attribute::Bold)
+                                .set_alignment(CellAlignment::Right),
+                            Cell::new(location),
+                        ]);
+                    }
+
                     println!("{table}\n");
                 })
                 .await;
@@ -120,6 +264,11 @@ async fn main() -> Result<()> {
             seen.list().await?.into_iter().for_each(|d| {
                 let t = match d.content {
                     Content::WebPage { .. } => "webpage",
+                    Content::Pdf { .. } => "pdf",
+                    Content::Book { .. } => "book",
+                    Content::Image { .. } => "image",
+                    Content::Text { .. } => "text",
+                    Content::Video { .. } => "video",
                 };
                 table.add_row(vec![d.uuid.to_string(), t.to_string(), d.title]);
             });
@@ -127,6 +276,156 @@ async fn main() -> Result<()> {
             println!("{table}");
         }
         Command::Recover(_) => seen::archive::recover(&seen).await?,
+        Command::Worker(Worker { exit_when_empty }) => {
+            seen::queue::work(&seen, exit_when_empty).await?
+        }
+        Command::Jobs => {
+            let mut table = Table::new();
+
+            table.load_preset(presets::NOTHING);
+
+            seen::queue::list(&seen).await?.into_iter().for_each(|j| {
+                let detail = j
+                    .document
+                    .map(|d| d.to_string())
+                    .or(j.outcome)
+                    .unwrap_or_default();
+                table.add_row(vec![
+                    j.id.to_string(),
+                    j.state.to_string(),
+                    j.attempts.to_string(),
+                    j.updated.format("%Y-%m-%d %H:%M").to_string(),
+                    j.url,
+                    detail,
+                ]);
+            });
+
+            println!("{table}");
+        }
+        Command::Import(Import::Bookmarks(ImportFile { file, batch })) => {
+            let html = String::from_utf8_lossy(&read_input(&file)?).into_owned();
+            add_entries(&seen, seen::import::bookmarks::parse(&html), batch).await;
+        }
+        Command::Import(Import::History(ImportHistory {
+            file,
+            min_visits,
+            min_duration,
+            batch,
+        })) => {
+            let filter = HistoryFilter {
+                min_visits,
+                min_duration,
+            };
+            let entries = seen::import::history::import(&seen, &file, &filter).await?;
+            add_entries(&seen, entries, batch).await;
+        }
+        Command::Import(Import::Pocket(ImportFile { file, batch })) => {
+            let html = String::from_utf8_lossy(&read_input(&file)?).into_owned();
+            add_entries(&seen, seen::import::pocket::parse(&html), batch).await;
+        }
+        Command::Import(Import::Instapaper(ImportFile { file, batch })) => {
+            let csv = String::from_utf8_lossy(&read_input(&file)?).into_owned();
+            add_entries(&seen, seen::import::instapaper::parse(&csv)?, batch).await;
+        }
+        Command::Import(Import::Pinboard(ImportFile { file, batch })) => {
+            let json = String::from_utf8_lossy(&read_input(&file)?).into_owned();
+            add_entries(&seen, seen::import::pinboard::parse(&json)?, batch).await;
+        }
+        Command::Import(Import::Warc(ImportWarc {
+            file,
+            tags,
+            dry_run,
+        })) => {
+            let reports = seen::import::warc::import(&seen, &file, &tags, dry_run).await?;
+            print_reports(&reports);
+        }
+        Command::Import(Import::Mbox(ImportMbox {
+            file,
+            tags,
+            dry_run,
+        })) => {
+            let reports = seen::import::mbox::import(&seen, &file, &tags, dry_run).await?;
+            print_reports(&reports);
+        }
+        Command::Export(Export::Warc(ExportWarc { file })) => {
+            let exported = seen::archive::export_warc(&seen, &file)?;
+            for (path, error) in &exported.skipped {
+                eprintln!("Skipped {}: {error}", path.display());
+            }
+            println!("Exported {} pages.", exported.pages);
+        }
+        Command::Feed(Feed::Add(FeedAdd { url, tags })) => {
+            let id = seen::feed::subscribe(&seen, &url, &tags).await?;
+            println!("Subscribed as feed {id}.");
+        }
+        Command::Feed(Feed::List) => {
+            let mut table = Table::new();
+
+            table.load_preset(presets::NOTHING);
+
+            seen::feed::subscriptions(&seen)
+                .await?
+                .into_iter()
+                .for_each(|f| {
+                    table.add_row(vec![
+                        f.id.to_string(),
+                        f.title.unwrap_or_default(),
+                        f.url,
+                        f.tags.join(", "),
+                        f.last_polled
+                            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+                            .unwrap_or_else(|| "never".to_string()),
+                    ]);
+                });
+
+            println!("{table}");
+        }
+        Command::Feed(Feed::Remove(FeedRemove { feed })) => {
+            if !seen::feed::unsubscribe(&seen, &feed).await? {
+                println!("No feed {feed}.");
+            }
+        }
+        Command::Feed(Feed::Poll(FeedPoll { jobs, no_archive })) => {
+            let reports = seen::feed::poll(&seen, jobs, !no_archive).await?;
+            print_reports(&reports);
+        }
+        Command::Crawl(Crawl {
+            url,
+            max_depth,
+            max_pages,
+            prefix,
+            no_sitemap,
+            tags,
+            no_archive,
+            dry_run,
+        }) => {
+            let options = CrawlOptions {
+                max_depth,
+                max_pages,
+                prefix,
+                sitemap: !no_sitemap,
+                tags,
+                archive: !no_archive,
+                dry_run,
+            };
+            let reports = seen::crawl::crawl(&seen, url, &options).await?;
+            print_reports(&reports);
+        }
+        Command::Refresh(Refresh {
+            uuid,
+            all: _,
+            older_than,
+            no_archive,
+            dry_run,
+        }) => {
+            let selection = match (uuid, older_than) {
+                (Some(uuid), _) => Selection::Document(uuid),
+                (None, Some(age)) => Selection::OlderThan(age),
+                (None, None) => Selection::All,
+            };
+            let reports = seen::refresh::refresh(&seen, &selection, !no_archive, dry_run).await?;
+            print_reports(&reports);
+        }
         Command::Settings(_) => {}
     }
 
@@ -143,6 +442,93 @@ struct Args {
     command: Command,
 }
 
+/// Read content of `file`, or standard input if it is `-`.
+fn read_input(file: &Path) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+
+    if file.as_os_str() == "-" {
+        std::io::stdin()
+            .read_to_end(&mut content)
+            .into_diagnostic()?;
+    } else {
+        content = std::fs::read(file).into_diagnostic()?;
+    }
+
+    Ok(content)
+}
+
+/// Add or queue all `entries` and print what happened to each of them.
+async fn add_entries(seen: &Seen, entries: Vec<Entry>, batch: Batch) {
+    let reports = if batch.background && !seen.always_in_foreground() {
+        seen::batch::enqueue_all(seen, entries, &batch.tags, !batch.no_archive).await
+    } else {
+        seen::batch::add_all(
+            seen,
+            entries,
+            &batch.tags,
+            batch.jobs,
+            !batch.no_archive,
+            batch.dry_run,
+        )
+        .await
+    };
+
+    print_reports(&reports);
+}
+
+/// Print table with outcome of each URL of a batch, followed by totals.
+fn print_reports(reports: &[Report]) {
+    let mut table = Table::new();
+
+    table.load_preset(presets::NOTHING);
+
+    let (mut added, mut updated, mut queued, mut skipped, mut failed) = (0, 0, 0, 0, 0);
+
+    for report in reports {
+        let (status, detail) = match &report.status {
+            Status::Added(uuid) => {
+                added += 1;
+                (
+                    "added",
+                    uuid.map(|u| u.to_string())
+                        .unwrap_or_else(|| "dry run".to_string()),
+                )
+            }
+            Status::Updated(uuid) => {
+                updated += 1;
+                (
+                    "updated",
+                    uuid.map(|u| u.to_string())
+                        .unwrap_or_else(|| "dry run".to_string()),
+                )
+            }
+            Status::Queued(id) => {
+                queued += 1;
+                ("queued", format!("job {id}"))
+            }
+            Status::Skipped(reason) => {
+                skipped += 1;
+                ("skipped", reason.clone())
+            }
+            Status::Failed(error) => {
+                failed += 1;
+                ("failed", error.clone())
+            }
+        };
+
+        table.add_row(vec![
+            Cell::new(status).add_attribute(Attribute::Bold),
+            Cell::new(&report.target),
+            Cell::new(detail),
+        ]);
+    }
+
+    println!("{table}\n");
+    println!(
+        "{added} added, {updated} updated, {queued} queued, {skipped} skipped, {failed} failed"
+    );
+}
+
 #[derive(Parser, Debug)]
 struct Get {
     /// Obtain document by UUID
@@ -157,13 +543,43 @@ struct Search {
 
 #[derive(Parser, Debug)]
 struct Add {
-    /// URL to remember
-    url: Uri,
+    /// URL or local file to remember
+    #[arg(value_parser = target_uri, required_unless_present_any = ["stdin", "FILE"])]
+    target: Option<Uri>,
+
+    /// URL of the page whose HTML is read from standard input
+    #[arg(long, id = "URL", conflicts_with = "target", requires = "stdin")]
+    url: Option<Uri>,
+
+    /// Read HTML of the page from standard input instead of downloading it (requires --url)
+    #[arg(long, default_value = "false", requires = "URL")]
+    stdin: bool,
+
+    /// Add all URLs listed in a file (`-` for standard input), one per line,
+    /// each optionally followed by its own tags
+    #[arg(
+        long,
+        id = "FILE",
+        conflicts_with_all = ["target", "stdin", "TRANSCRIPT", "REFERRER"]
+    )]
+    from_file: Option<PathBuf>,
+
+    /// How many URLs from the file are processed at once
+    #[arg(short, long, default_value = "4", requires = "FILE")]
+    jobs: usize,
 
     /// Add tag (can be used repeatedly)
     #[arg(short, long = "tag", id = "TAG")]
     tags: Vec<String>,
 
+    /// Index URL as a video using WebVTT or SRT transcript from URL or file.
+    #[arg(long, id = "TRANSCRIPT")]
+    transcript: Option<TranscriptLocation>,
+
+    /// Page on which the image was found, to obtain its alternative text.
+    #[arg(long, id = "REFERRER")]
+    referrer: Option<Uri>,
+
     /// Do not archive this source.
     #[arg(long, default_value = "false")]
     no_archive: bool,
@@ -171,6 +587,224 @@ struct Add {
     /// Do not index or archive anything.
     #[arg(short = 'n', long, default_value = "false")]
     dry_run: bool,
+
+    /// Only queue the URL, to be added later by `seen worker`.
+    #[arg(long, default_value = "false", conflicts_with_all = ["stdin", "dry_run"])]
+    background: bool,
+}
+
+/// Options of adding many URLs at once.
+#[derive(Parser, Debug)]
+struct Batch {
+    /// Add tag to all URLs (can be used repeatedly)
+    #[arg(short, long = "tag", id = "TAG")]
+    tags: Vec<String>,
+
+    /// How many URLs are processed at once
+    #[arg(short, long, default_value = "4")]
+    jobs: usize,
+
+    /// Do not archive the sources.
+    #[arg(long, default_value = "false")]
+    no_archive: bool,
+
+    /// Do not index or archive anything.
+    #[arg(short = 'n', long, default_value = "false")]
+    dry_run: bool,
+
+    /// Only queue the URLs, to be added later by `seen worker`.
+    #[arg(long, default_value = "false", conflicts_with = "dry_run")]
+    background: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Import {
+    /// Import bookmarks exported from a browser as HTML (Netscape bookmark file).
+    Bookmarks(ImportFile),
+    /// Import pages from a copy of Firefox (places.sqlite) or Chromium (History) history.
+    History(ImportHistory),
+    /// Import articles from Pocket HTML export.
+    Pocket(ImportFile),
+    /// Import articles from Instapaper CSV export.
+    Instapaper(ImportFile),
+    /// Import bookmarks from Pinboard JSON export.
+    Pinboard(ImportFile),
+    /// Index HTML pages captured in a WARC file, without downloading them again.
+    Warc(ImportWarc),
+    /// Index email messages (e. g. newsletters) from an mbox archive or .eml file.
+    Mbox(ImportMbox),
+}
+
+#[derive(Parser, Debug)]
+struct ImportFile {
+    /// File to import (`-` for standard input)
+    file: PathBuf,
+
+    #[command(flatten)]
+    batch: Batch,
+}
+
+#[derive(Parser, Debug)]
+struct ImportHistory {
+    /// History database of the browser
+    file: PathBuf,
+
+    /// Import only pages visited at least this many times
+    #[arg(long, default_value = "1")]
+    min_visits: i64,
+
+    /// Import only pages open for at least this many seconds (Chromium only)
+    #[arg(long)]
+    min_duration: Option<i64>,
+
+    #[command(flatten)]
+    batch: Batch,
+}
+
+#[derive(Parser, Debug)]
+struct ImportWarc {
+    /// WARC file, possibly compressed by gzip
+    file: PathBuf,
+
+    /// Add tag to all pages (can be used repeatedly)
+    #[arg(short, long = "tag", id = "TAG")]
+    tags: Vec<String>,
+
+    /// Do not index anything.
+    #[arg(short = 'n', long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+struct ImportMbox {
+    /// Mbox archive or a single .eml file
+    file: PathBuf,
+
+    /// Add tag to all messages (can be used repeatedly)
+    #[arg(short, long = "tag", id = "TAG")]
+    tags: Vec<String>,
+
+    /// Do not index anything.
+    #[arg(short = 'n', long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Export {
+    /// Export archived web pages as WARC file (compressed if its name ends with `.gz`).
+    Warc(ExportWarc),
+}
+
+#[derive(Parser, Debug)]
+struct ExportWarc {
+    /// WARC file to write
+    file: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+enum Feed {
+    /// Subscribe to a feed.
+    Add(FeedAdd),
+    /// List subscribed feeds.
+    List,
+    /// Unsubscribe from a feed.
+    Remove(FeedRemove),
+    /// Add new entries of all subscribed feeds.
+    Poll(FeedPoll),
+}
+
+#[derive(Parser, Debug)]
+struct FeedAdd {
+    /// URL of RSS or Atom feed
+    url: Uri,
+
+    /// Add tag to all entries of the feed (can be used repeatedly)
+    #[arg(short, long = "tag", id = "TAG")]
+    tags: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+struct FeedRemove {
+    /// ID or URL of the feed
+    feed: String,
+}
+
+#[derive(Parser, Debug)]
+struct FeedPoll {
+    /// How many entries are processed at once
+    #[arg(short, long, default_value = "4")]
+    jobs: usize,
+
+    /// Do not archive the entries.
+    #[arg(long, default_value = "false")]
+    no_archive: bool,
+}
+
+#[derive(Parser, Debug)]
+struct Crawl {
+    /// URL from which the crawl starts
+    url: Uri,
+
+    /// How many links away from the starting URL to go
+    #[arg(long, default_value = "2")]
+    max_depth: usize,
+
+    /// How many pages to download at most
+    #[arg(long, default_value = "100")]
+    max_pages: usize,
+
+    /// Only crawl URLs starting with this prefix
+    #[arg(long)]
+    prefix: Option<String>,
+
+    /// Do not crawl URLs listed in sitemaps of the site.
+    #[arg(long, default_value = "false")]
+    no_sitemap: bool,
+
+    /// Add tag to all crawled pages (can be used repeatedly)
+    #[arg(short, long = "tag", id = "TAG")]
+    tags: Vec<String>,
+
+    /// Do not archive the pages.
+    #[arg(long, default_value = "false")]
+    no_archive: bool,
+
+    /// Only report what would be added.
+    #[arg(short = 'n', long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+struct Refresh {
+    /// Refresh only document with this UUID
+    #[arg(
+        required_unless_present_any = ["all", "older_than"],
+        conflicts_with_all = ["all", "older_than"]
+    )]
+    uuid: Option<Uuid>,
+
+    /// Refresh all documents
+    #[arg(long, default_value = "false", conflicts_with = "older_than")]
+    all: bool,
+
+    /// Refresh documents seen longer ago than this (e. g. 30d, 12h, 2w)
+    #[arg(long, value_parser = parse_age)]
+    older_than: Option<chrono::Duration>,
+
+    /// Do not archive the refreshed documents.
+    #[arg(long, default_value = "false")]
+    no_archive: bool,
+
+    /// Only report what would be updated.
+    #[arg(short = 'n', long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Parser, Debug)]
+struct Worker {
+    /// Stop once there are no more jobs to do, instead of waiting for new ones.
+    #[arg(long, default_value = "false")]
+    exit_when_empty: bool,
 }
 
 #[derive(Parser, Debug)]
@@ -215,6 +849,7 @@ pub struct SetSettings {
 }
 
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
 enum Command {
     /// Add new URL.
     Add(Add),
@@ -228,6 +863,23 @@ enum Command {
     List,
     /// Recover archive.
     Recover(Recover),
+    /// Import URLs from other applications.
+    #[clap(subcommand)]
+    Import(Import),
+    /// Follow RSS and Atom feeds.
+    #[clap(subcommand)]
+    Feed(Feed),
+    /// Export archive into formats of other applications.
+    #[clap(subcommand)]
+    Export(Export),
+    /// Add pages of a site, following links from a starting URL.
+    Crawl(Crawl),
+    /// Download indexed documents again and re-index changed ones.
+    Refresh(Refresh),
+    /// Add sources queued by `add --background`.
+    Worker(Worker),
+    /// List queued jobs and their outcomes.
+    Jobs,
     /// Manage settings.
     #[clap(subcommand)]
     Settings(Settings),
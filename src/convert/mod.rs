@@ -2,9 +2,11 @@ use miette::Diagnostic;
 use thiserror::Error;
 
 pub mod md;
+pub mod ocr;
 
 /// Errors that happened during format conversions.
 #[derive(Debug, Error, Diagnostic)]
+#[allow(clippy::enum_variant_names)]
 pub enum ConvertError {
     #[error(
         "Command '{0}' not found. For proper functionality, it has to be installed on the system."
@@ -14,6 +16,12 @@ pub enum ConvertError {
     #[error("Command resulted in an error.")]
     CommandError(#[from] std::io::Error),
 
+    #[error("Command failed ({status}): {stderr}")]
+    CommandFailed {
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+
     #[error("Command output produced an error.")]
-    CommandOutput(Box<dyn std::error::Error>),
+    CommandOutput(Box<dyn std::error::Error + Send + Sync>),
 }
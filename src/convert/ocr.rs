@@ -0,0 +1,73 @@
+use std::io::{ErrorKind, Write};
+use std::process::Stdio;
+
+use tempfile::NamedTempFile;
+use tokio::process::Command;
+
+use super::ConvertError;
+
+/// Recognizes text in `image` by running external `command` (e. g. `tesseract`).
+/// First element of `command` is the program, the rest are its arguments, in which
+/// `{file}` is replaced by path to a temporary file with the image. Standard output
+/// of the command is returned, unless the command fails.
+pub async fn image_to_text(command: &[String], image: &[u8]) -> Result<String, ConvertError> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| ConvertError::CommandNotFound(String::new()))?;
+
+    let mut file = NamedTempFile::new()?;
+    file.write_all(image)?;
+
+    let path = file.path().to_string_lossy();
+
+    let output = Command::new(program)
+        .args(args.iter().map(|a| a.replace("{file}", &path)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|e| {
+            if e.kind() == ErrorKind::NotFound {
+                ConvertError::CommandNotFound(program.to_string())
+            } else {
+                ConvertError::CommandError(e)
+            }
+        })?;
+
+    if !output.status.success() {
+        return Err(ConvertError::CommandFailed {
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+
+    String::from_utf8(output.stdout).map_err(|e| ConvertError::CommandOutput(Box::new(e)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::image_to_text;
+    use crate::convert::ConvertError;
+
+    #[tokio::test]
+    async fn failing_command() {
+        let command = [
+            "sh".to_string(),
+            "-c".to_string(),
+            "echo missing language >&2; exit 1".to_string(),
+        ];
+        let error = image_to_text(&command, b"image").await.unwrap_err();
+        assert!(matches!(
+            error,
+            ConvertError::CommandFailed { stderr, .. } if stderr == "missing language"
+        ));
+    }
+
+    #[tokio::test]
+    async fn runs_configured_command() {
+        let command = ["cat".to_string(), "{file}".to_string()];
+        let text = image_to_text(&command, b"recognized text").await.unwrap();
+        assert_eq!(text, "recognized text");
+    }
+}
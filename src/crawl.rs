@@ -0,0 +1,439 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::Local;
+use indicatif::{ProgressBar, ProgressStyle};
+use isahc::http::Uri;
+use kuchiki::traits::TendrilSink;
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::archive::archive_source;
+use crate::batch::{describe, Report, Status};
+use crate::job::{self, download_source, index_source, JobError, SourceHints};
+use crate::source::Source;
+use crate::url_preferences::{self, Preferences, UrlPreferences};
+use crate::Seen;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum CrawlError {
+    #[error("Crawl can only start from HTTP(S) URL.")]
+    InvalidSeed,
+}
+
+/// Bounds of a crawl and what to do with crawled pages.
+#[derive(Debug)]
+pub struct CrawlOptions {
+    /// How many links away from the seed the crawl may get.
+    pub max_depth: usize,
+    /// How many sources are downloaded at most.
+    pub max_pages: usize,
+    /// Only URLs starting with this prefix are crawled (besides staying
+    /// on the host of the seed).
+    pub prefix: Option<String>,
+    /// Whether to crawl URLs listed in sitemaps of the site.
+    pub sitemap: bool,
+    pub tags: Vec<String>,
+    pub archive: bool,
+    pub dry_run: bool,
+}
+
+/// Crawl site starting from `seed`, following links breadth first within
+/// bounds given by `options` and rules of robots.txt. Every crawled source
+/// is added like any other. Sources already indexed are not indexed again,
+/// but their links are followed.
+pub async fn crawl(
+    seen: &Seen,
+    seed: Uri,
+    options: &CrawlOptions,
+) -> Result<Vec<Report>, CrawlError> {
+    let seed = normalize(&seed.to_string()).ok_or(CrawlError::InvalidSeed)?;
+    let host = seed.host_str().map(|h| h.to_string());
+
+    let in_scope = |url: &url::Url| {
+        matches!(url.scheme(), "http" | "https")
+            && url.host_str() == host.as_deref()
+            && options
+                .prefix
+                .as_ref()
+                .map(|p| url.as_str().starts_with(p.as_str()))
+                .unwrap_or(true)
+    };
+
+    if !in_scope(&seed) {
+        return Err(CrawlError::InvalidSeed);
+    }
+
+    let robots = seen
+        .politeness
+        .robots(&seen.http_client, &to_uri(&seed))
+        .await;
+
+    let mut visited = HashSet::from([seed.to_string()]);
+    let mut frontier = VecDeque::from([(seed.clone(), 0)]);
+
+    // Pages listed in sitemaps are one step away from the seed.
+    if options.sitemap && options.max_depth >= 1 {
+        let mut sitemaps = robots.sitemaps.clone();
+        if sitemaps.is_empty() {
+            sitemaps.extend(seed.join("/sitemap.xml").map(|u| u.to_string()));
+        }
+
+        for url in fetch_sitemaps(seen, &sitemaps).await {
+            if let Some(url) = normalize(&url).filter(|u| in_scope(u)) {
+                if visited.insert(url.to_string()) {
+                    frontier.push_back((url, 1));
+                }
+            }
+        }
+    }
+
+    let pb = ProgressBar::new(options.max_pages as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{bar:40.green/yellow} {pos:>7}/{len:7} {wide_msg}").unwrap(),
+    );
+
+    let mut reports = vec![];
+    let mut downloaded = 0;
+
+    while let Some((url, depth)) = frontier.pop_front() {
+        if downloaded >= options.max_pages {
+            break;
+        }
+
+        let target = url.to_string();
+        let uri = to_uri(&url);
+
+        let preferences = match url_preferences::for_url(&uri, seen).await {
+            Some(UrlPreferences::Blacklist) => {
+                reports.push(Report {
+                    target,
+                    status: Status::Skipped("blacklisted".to_string()),
+                });
+                continue;
+            }
+            Some(UrlPreferences::Preferences(p)) => p,
+            None => Preferences::default(),
+        };
+
+        pb.set_message(target.clone());
+
+        // Robots.txt is respected by the download itself.
+        let source = download_source(
+            seen,
+            &uri,
+            &preferences,
+            &SourceHints::default(),
+            ProgressBar::hidden(),
+        )
+        .await;
+
+        if let Err(JobError::DisallowedByRobots(_)) = source {
+            reports.push(Report {
+                target,
+                status: Status::Skipped("disallowed by robots.txt".to_string()),
+            });
+            continue;
+        }
+
+        downloaded += 1;
+        pb.inc(1);
+
+        let (source, redirects) = match source {
+            Ok(downloaded) => downloaded,
+            Err(JobError::MimeNotSupported(mime)) => {
+                reports.push(Report {
+                    target,
+                    status: Status::Skipped(format!("content type {mime} not supported")),
+                });
+                continue;
+            }
+            Err(e) => {
+                reports.push(Report {
+                    target,
+                    status: Status::Failed(describe(&e)),
+                });
+                continue;
+            }
+        };
+
+        if depth < options.max_depth {
+            if let Source::Page(page) = &source {
+                let base = normalize(&page.url.to_string()).unwrap_or_else(|| url.clone());
+                for link in links(&page.body, &base) {
+                    if in_scope(&link) && visited.insert(link.to_string()) {
+                        frontier.push_back((link, depth + 1));
+                    }
+                }
+            }
+        }
+
+        let status = add_source(seen, &uri, &redirects, source, &preferences, options).await;
+        reports.push(Report { target, status });
+    }
+
+    pb.finish_and_clear();
+
+    Ok(reports)
+}
+
+/// Archive and index crawled source, unless it is already indexed.
+async fn add_source(
+    seen: &Seen,
+    url: &Uri,
+    redirects: &[Uri],
+    source: Source,
+    preferences: &Preferences,
+    options: &CrawlOptions,
+) -> Status {
+    // Several links may redirect to the same document.
+    for u in std::iter::once(url).chain(redirects) {
+        match job::existing_document(seen, u).await {
+            Ok(Some(_)) => return Status::Skipped("already indexed".to_string()),
+            Ok(None) => (),
+            Err(e) => return Status::Failed(describe(&e)),
+        }
+    }
+
+    if options.dry_run {
+        return Status::Added(None);
+    }
+
+    let metadata = HashMap::from([(
+        "tag".to_string(),
+        serde_json::to_value(&options.tags).unwrap(),
+    )]);
+    let time = Local::now();
+
+    if options.archive {
+        archive_source(seen, &source, &metadata, time).await;
+    }
+
+    match index_source(
+        seen,
+        url,
+        redirects,
+        source,
+        preferences,
+        metadata,
+        time,
+        &options.tags,
+    )
+    .await
+    {
+        Ok(uuid) => Status::Added(Some(uuid)),
+        Err(e) => Status::Failed(describe(&e)),
+    }
+}
+
+/// Download sitemaps and return URLs listed in them. Sitemap indexes are
+/// followed one level deep. Sitemaps which cannot be read are ignored.
+async fn fetch_sitemaps(seen: &Seen, sitemaps: &[String]) -> Vec<String> {
+    let mut urls = vec![];
+
+    for sitemap in sitemaps {
+        let (pages, nested) = fetch_sitemap(seen, sitemap).await;
+        urls.extend(pages);
+
+        for sitemap in nested {
+            urls.extend(fetch_sitemap(seen, &sitemap).await.0);
+        }
+    }
+
+    urls
+}
+
+/// Download sitemap at `url` like pages (see [`job::fetch`]) and read it.
+async fn fetch_sitemap(seen: &Seen, url: &str) -> (Vec<String>, Vec<String>) {
+    let url = match url.parse::<Uri>() {
+        Ok(url) => url,
+        Err(_) => return Default::default(),
+    };
+
+    match job::fetch(seen, &url).await {
+        Ok((_, _, body)) => parse_sitemap(&String::from_utf8_lossy(&body)),
+        Err(_) => Default::default(),
+    }
+}
+
+/// Read URLs of pages and URLs of nested sitemaps from a sitemap
+/// (`urlset`) or sitemap index (`sitemapindex`).
+fn parse_sitemap(xml: &str) -> (Vec<String>, Vec<String>) {
+    let document = match roxmltree::Document::parse(xml) {
+        Ok(document) => document,
+        Err(_) => return Default::default(),
+    };
+    let root = document.root_element();
+
+    let locations = root
+        .descendants()
+        .filter(|n| n.tag_name().name() == "loc")
+        .filter_map(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    match root.tag_name().name() {
+        "sitemapindex" => (vec![], locations),
+        _ => (locations, vec![]),
+    }
+}
+
+/// Absolute URLs of links (`<a href>`) on page with HTML `body` at `base`.
+fn links(body: &str, base: &url::Url) -> Vec<url::Url> {
+    let document = kuchiki::parse_html().one(body);
+
+    // `<base href>` changes what relative links are relative to.
+    let base = document
+        .select_first("base[href]")
+        .ok()
+        .and_then(|b| {
+            let href = b.attributes.borrow().get("href")?.to_string();
+            base.join(&href).ok()
+        })
+        .unwrap_or_else(|| base.clone());
+
+    let links = match document.select("a[href]") {
+        Ok(links) => links,
+        Err(_) => return vec![],
+    };
+
+    links
+        .filter(|a| {
+            let attributes = a.attributes.borrow();
+            let rel = attributes.get("rel").unwrap_or("");
+            !rel.split_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("nofollow"))
+        })
+        .filter_map(|a| {
+            let href = a.attributes.borrow().get("href")?.trim().to_string();
+            normalize(base.join(&href).ok()?.as_str())
+        })
+        .collect()
+}
+
+/// Parse URL without its fragment, which does not identify another page.
+fn normalize(url: &str) -> Option<url::Url> {
+    let mut url = url::Url::parse(url).ok()?;
+    url.set_fragment(None);
+    Some(url)
+}
+
+fn to_uri(url: &url::Url) -> Uri {
+    url.as_str().parse().unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, OnceLock};
+
+    use super::{crawl, links, parse_sitemap, CrawlOptions};
+    use crate::testing::{response, serve};
+    use crate::Seen;
+
+    #[tokio::test]
+    async fn sitemap_within_depth() {
+        // Sitemaps list absolute URLs, which are known once the server runs.
+        let origin = Arc::new(OnceLock::<String>::new());
+        let sitemap_origin = origin.clone();
+
+        let address = serve(move |path| match path {
+            "/" => response("200 OK", &[("Content-Type", "text/html")], "<p>Home</p>"),
+            "/sitemap.xml" => response(
+                "200 OK",
+                &[("Content-Type", "application/xml")],
+                &format!(
+                    "<urlset><url><loc>{}listed</loc></url></urlset>",
+                    sitemap_origin.get().unwrap()
+                ),
+            ),
+            "/listed" => response("200 OK", &[("Content-Type", "text/html")], "<p>Listed</p>"),
+            _ => response("404 Not Found", &[], ""),
+        })
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+        let seed = format!("http://{address}/");
+        origin.set(seed.clone()).unwrap();
+        let options = |max_depth| CrawlOptions {
+            max_depth,
+            max_pages: 10,
+            prefix: None,
+            sitemap: true,
+            tags: vec![],
+            archive: false,
+            dry_run: true,
+        };
+
+        let targets = |reports: Vec<crate::batch::Report>| {
+            reports.into_iter().map(|r| r.target).collect::<Vec<_>>()
+        };
+
+        let shallow = crawl(&seen, seed.parse().unwrap(), &options(0))
+            .await
+            .unwrap();
+        assert_eq!(targets(shallow), vec![seed.clone()]);
+
+        let deep = crawl(&seen, seed.parse().unwrap(), &options(1))
+            .await
+            .unwrap();
+        assert_eq!(targets(deep), vec![seed.clone(), format!("{seed}listed")]);
+    }
+
+    #[test]
+    fn page_links() {
+        let html = r#"<html><body>
+<a href="/docs/#intro">Docs</a>
+<a href="other.html">Other</a>
+<a href="https://elsewhere.org/">Elsewhere</a>
+<a href="/login" rel="nofollow">Log in</a>
+<a href="mailto:me@example.com">Mail</a>
+</body></html>"#;
+
+        let base = url::Url::parse("https://example.com/blog/post").unwrap();
+        let links = links(html, &base)
+            .into_iter()
+            .map(|u| u.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            links,
+            vec![
+                "https://example.com/docs/",
+                "https://example.com/blog/other.html",
+                "https://elsewhere.org/",
+                "mailto:me@example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn sitemap_and_index() {
+        let sitemap = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/</loc></url>
+  <url><loc> https://example.com/about </loc><lastmod>2023-01-01</lastmod></url>
+</urlset>"#;
+        assert_eq!(
+            parse_sitemap(sitemap),
+            (
+                vec![
+                    "https://example.com/".to_string(),
+                    "https://example.com/about".to_string()
+                ],
+                vec![]
+            )
+        );
+
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/sitemap-posts.xml</loc></sitemap>
+</sitemapindex>"#;
+        assert_eq!(
+            parse_sitemap(index),
+            (
+                vec![],
+                vec!["https://example.com/sitemap-posts.xml".to_string()]
+            )
+        );
+    }
+}
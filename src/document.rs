@@ -6,6 +6,8 @@ use serde_json::Value;
 use uuid::Uuid;
 
 use crate::options::SeenOptions;
+use crate::source::video::Cue;
+use crate::source::Chapter;
 use crate::url_preferences::Preferences;
 
 /// Marks types that can be turned into [`documents`](Document).
@@ -52,12 +54,115 @@ pub enum Content {
         /// is present, `text` will be used.
         rich_text: Option<String>,
     },
+    Pdf {
+        /// Text of all pages of the document.
+        text: String,
+
+        /// Text of individual pages.
+        pages: Vec<String>,
+    },
+    Book {
+        /// Text of all chapters of the book.
+        text: String,
+
+        /// Chapters in reading order.
+        chapters: Vec<Chapter>,
+    },
+    Image {
+        /// All text describing the image (alternative text, description,
+        /// recognized text).
+        text: String,
+
+        /// Content type of the image.
+        mime: String,
+
+        /// The image itself.
+        data: Vec<u8>,
+    },
+    Text {
+        /// The text as it is.
+        text: String,
+
+        /// Markdown for displaying, if the text is Markdown.
+        rich_text: Option<String>,
+    },
+    Video {
+        /// Transcript of the video.
+        text: String,
+
+        /// Timed cues of the transcript.
+        cues: Vec<Cue>,
+    },
+}
+
+/// Position within content of a document.
+#[derive(Clone, Debug)]
+pub enum Location {
+    /// Page number, starting from 1.
+    Page(usize),
+    /// Time in milliseconds from the beginning.
+    Time(u64),
+    /// Chapter number, starting from 1, and title of the chapter.
+    Chapter(usize, Option<String>),
 }
 
 impl Content {
     pub fn plain_text(&self) -> &str {
         match self {
             Content::WebPage { text, .. } => text,
+            Content::Pdf { text, .. } => text,
+            Content::Book { text, .. } => text,
+            Content::Image { text, .. } => text,
+            Content::Text { text, .. } => text,
+            Content::Video { text, .. } => text,
+        }
+    }
+
+    /// Find where `fragment` of the plain text (e. g. a search snippet)
+    /// occurs within the content. Returns `None` if the fragment cannot be found
+    /// or if the content has no notion of position.
+    pub fn locate(&self, fragment: &str) -> Option<Location> {
+        if fragment.is_empty() {
+            return None;
+        }
+
+        let offset = self.plain_text().find(fragment)?;
+
+        match self {
+            Content::WebPage { .. } | Content::Image { .. } | Content::Text { .. } => None,
+            // Pages are joined by two new lines.
+            Content::Pdf { pages, .. } => {
+                locate_part(pages.iter().map(|p| p.len()), 2, offset).map(|i| Location::Page(i + 1))
+            }
+            // Chapters are joined by two new lines.
+            Content::Book { chapters, .. } => {
+                locate_part(chapters.iter().map(|c| c.text.len()), 2, offset)
+                    .map(|i| Location::Chapter(i + 1, chapters[i].title.clone()))
+            }
+            // Cues are joined by one new line, see `transcript_text`.
+            Content::Video { cues, .. } => {
+                locate_part(cues.iter().map(|c| c.text.len()), 1, offset)
+                    .map(|i| Location::Time(cues[i].start))
+            }
+        }
+    }
+}
+
+/// Find index of the part in which `offset` lies, when parts of given lengths
+/// are joined with separator of length `separator`.
+fn locate_part(
+    lengths: impl Iterator<Item = usize>,
+    separator: usize,
+    offset: usize,
+) -> Option<usize> {
+    let mut end = 0;
+
+    for (i, len) in lengths.enumerate() {
+        end += len + separator;
+        if offset < end {
+            return Some(i);
         }
     }
+
+    None
 }
@@ -2,6 +2,7 @@ use chromiumoxide::cdp::browser_protocol::page::{CaptureScreenshotParams, PrintT
 use chromiumoxide::{Browser, BrowserConfig};
 use futures::StreamExt;
 
+#[allow(dead_code)]
 pub async fn pdf() {
     let (browser, mut handler) = Browser::launch(BrowserConfig::builder().build().unwrap())
         .await
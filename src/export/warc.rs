@@ -0,0 +1,227 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use encoding_rs::Encoding;
+use isahc::http::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING};
+use isahc::http::HeaderValue;
+use miette::Diagnostic;
+use mime::Mime;
+use thiserror::Error;
+
+use crate::archive::{Archived, ArchivedSource};
+use crate::batch::describe;
+use crate::source::Page;
+use crate::warc::WarcWriter;
+use crate::Seen;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ExportError {
+    #[error("Could not read archive or write the export.")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not decode archive file.")]
+    Decode(#[from] serde_json::Error),
+}
+
+/// Outcome of an export.
+#[derive(Debug)]
+pub struct Exported {
+    /// Number of exported pages.
+    pub pages: usize,
+    /// Archive files which could not be read, with the reason.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+/// Write every archived web page into WARC file at `path` as a pair of `request`
+/// and `response` records, preceded by a `warcinfo` record (which also notes that
+/// statuses of the responses are made up). If the name of the file
+/// ends with `.gz`, each record is compressed by gzip. Archive files which cannot
+/// be read are skipped.
+pub fn export_warc(seen: &Seen, path: &Path) -> Result<Exported, ExportError> {
+    let gzip = path.extension().map(|e| e == "gz").unwrap_or(false);
+    let mut writer = WarcWriter::new(BufWriter::new(File::create(path)?), gzip);
+
+    let filename = path
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let info = format!(
+        "software: seen/{}\r\nformat: WARC File Format 1.0\r\n\
+         description: Status of responses is not archived, each is written as 200 OK.\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let info_id = writer.write_record(
+        "warcinfo",
+        Utc::now(),
+        &[
+            ("WARC-Filename", &filename),
+            ("Content-Type", "application/warc-fields"),
+        ],
+        info.as_bytes(),
+    )?;
+
+    // Archive files are named by time of archiving.
+    let mut files = std::fs::read_dir(seen.archive_dir())?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut pages = 0;
+    let mut skipped = vec![];
+
+    for file in files {
+        let archived = match read_archived(&file) {
+            Ok(archived) => archived,
+            Err(e) => {
+                skipped.push((file, describe(&e)));
+                continue;
+            }
+        };
+
+        let page = match archived.source {
+            ArchivedSource::Page(page) => page,
+            _ => continue,
+        };
+
+        let date = archived.time.with_timezone(&Utc);
+        let url = page.url.to_string();
+
+        let response_id = writer.write_record(
+            "response",
+            date,
+            &[
+                ("WARC-Target-URI", &url),
+                ("WARC-Warcinfo-ID", &info_id),
+                ("Content-Type", "application/http; msgtype=response"),
+            ],
+            &http_response(&page),
+        )?;
+
+        writer.write_record(
+            "request",
+            date,
+            &[
+                ("WARC-Target-URI", &url),
+                ("WARC-Warcinfo-ID", &info_id),
+                ("WARC-Concurrent-To", &response_id),
+                ("Content-Type", "application/http; msgtype=request"),
+            ],
+            &http_request(&page),
+        )?;
+
+        pages += 1;
+    }
+
+    writer.flush()?;
+
+    Ok(Exported { pages, skipped })
+}
+
+fn read_archived(file: &Path) -> Result<Archived, ExportError> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(file)?)?)
+}
+
+/// HTTP response as it would have been returned for `page`. Status of the response
+/// is not archived, only successful responses are, however, so `200 OK` is written.
+/// The body is archived decompressed, so headers describing its content encoding
+/// are left out. It is encoded back to the character encoding in which it was
+/// returned, except for UTF-16, which cannot be encoded to and is written as UTF-8
+/// with the charset in Content-Type changed accordingly.
+fn http_response(page: &Page) -> Vec<u8> {
+    let (body, encoding, _) = page.encoding().encode(&page.body);
+    let recoded = encoding != page.encoding();
+
+    let mut response = b"HTTP/1.1 200 OK\r\n".to_vec();
+    let mut header = |name: &str, value: &[u8]| {
+        response.extend_from_slice(name.as_bytes());
+        response.extend_from_slice(b": ");
+        response.extend_from_slice(value);
+        response.extend_from_slice(b"\r\n");
+    };
+
+    for (name, value) in page.headers.iter() {
+        if name == CONTENT_ENCODING || name == TRANSFER_ENCODING || name == CONTENT_LENGTH {
+            continue;
+        }
+        if name == CONTENT_TYPE && recoded {
+            continue;
+        }
+        header(name.as_str(), value.as_bytes());
+    }
+
+    if recoded {
+        let content_type = with_charset(page.headers.get(CONTENT_TYPE), encoding);
+        header(CONTENT_TYPE.as_str(), content_type.as_bytes());
+    }
+
+    response.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+    response.extend_from_slice(&body);
+
+    response
+}
+
+/// Content-Type `value` (HTML if missing or invalid) with charset set to `encoding`.
+fn with_charset(value: Option<&HeaderValue>, encoding: &'static Encoding) -> String {
+    let mime = value
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<Mime>().ok())
+        .unwrap_or(mime::TEXT_HTML);
+
+    let mut content_type = mime.essence_str().to_string();
+    for (name, value) in mime.params().filter(|(n, _)| *n != mime::CHARSET) {
+        content_type.push_str(&format!("; {name}=\"{value}\""));
+    }
+    content_type.push_str(&format!("; charset={}", encoding.name()));
+
+    content_type
+}
+
+/// HTTP request with which `page` would have been obtained.
+fn http_request(page: &Page) -> Vec<u8> {
+    let path = page.url.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let host = page.url.authority().map(|a| a.as_str()).unwrap_or_default();
+
+    format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n\r\n").into_bytes()
+}
+
+#[cfg(test)]
+mod test {
+    use isahc::http::header::CONTENT_TYPE;
+    use isahc::http::{HeaderMap, HeaderValue};
+
+    use super::http_response;
+    use crate::source::make_page;
+    use crate::warc::parse_response;
+
+    #[test]
+    fn utf16_page_as_utf8() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=UTF-16LE"),
+        );
+        let body = "<p>Žluťoučký kůň</p>"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let page = make_page("https://example.com/".parse().unwrap(), headers, body);
+
+        let response = parse_response(&http_response(&page)).unwrap();
+
+        assert_eq!(response.body, "<p>Žluťoučký kůň</p>".as_bytes());
+        assert_eq!(
+            response
+                .headers
+                .get_all(CONTENT_TYPE)
+                .iter()
+                .collect::<Vec<_>>(),
+            vec!["text/html; charset=UTF-8"]
+        );
+    }
+}
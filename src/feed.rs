@@ -0,0 +1,365 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Local};
+use isahc::http::Uri;
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::batch::{self, Entry, Report, Status};
+use crate::job::{self, JobError};
+use crate::Seen;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum FeedError {
+    #[error("Could not download feed.")]
+    Download(#[from] Box<JobError>),
+
+    #[error("Feed is neither RSS nor Atom: {0}")]
+    Invalid(String),
+
+    #[error("Database error.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// Feed to which we are subscribed.
+#[derive(Debug)]
+pub struct Subscription {
+    pub id: i64,
+    pub url: String,
+    /// Title of the feed, known once it is polled.
+    pub title: Option<String>,
+    /// Tags given to every entry of the feed.
+    pub tags: Vec<String>,
+    pub last_polled: Option<DateTime<Local>>,
+}
+
+/// Content of RSS or Atom feed.
+#[derive(Debug, PartialEq)]
+pub struct Feed {
+    pub title: Option<String>,
+    pub entries: Vec<FeedEntry>,
+}
+
+/// Entry (item) of a feed.
+#[derive(Debug, PartialEq)]
+pub struct FeedEntry {
+    /// Identifier of the entry (`guid` or `id`), or its URL if there is none.
+    pub id: String,
+    /// Absolute URL of the entry.
+    pub url: String,
+    pub title: Option<String>,
+}
+
+/// Subscribe to feed at `url`, whose entries will get `tags`. Returns ID
+/// of the subscription.
+pub async fn subscribe(seen: &Seen, url: &Uri, tags: &[String]) -> Result<i64, FeedError> {
+    let url = url.to_string();
+    let tags = serde_json::to_string(tags).unwrap();
+    let now = Local::now();
+
+    let id = sqlx::query!(
+        "INSERT INTO feeds (url, tags, added) VALUES (?, ?, ?)",
+        url,
+        tags,
+        now
+    )
+    .execute(&seen.pool)
+    .await?
+    .last_insert_rowid();
+
+    Ok(id)
+}
+
+/// Remove subscription identified by its ID or URL. Returns whether it existed.
+pub async fn unsubscribe(seen: &Seen, feed: &str) -> Result<bool, FeedError> {
+    let id = feed.parse::<i64>().unwrap_or(-1);
+
+    let removed = sqlx::query!("DELETE FROM feeds WHERE id = ? OR url = ?", id, feed)
+        .execute(&seen.pool)
+        .await?
+        .rows_affected();
+
+    Ok(removed > 0)
+}
+
+/// All subscriptions.
+pub async fn subscriptions(seen: &Seen) -> Result<Vec<Subscription>, FeedError> {
+    let rows = sqlx::query!(
+        r#"SELECT id, url, title, tags, last_polled AS "last_polled: DateTime<Local>" FROM feeds ORDER BY id"#
+    )
+    .fetch_all(&seen.pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| Subscription {
+            id: r.id,
+            url: r.url,
+            title: r.title,
+            tags: serde_json::from_str(&r.tags).unwrap_or_default(),
+            last_polled: r.last_polled,
+        })
+        .collect())
+}
+
+/// Download all subscribed feeds and add their entries which were not added yet.
+/// Entries which could not be added are tried again on the next poll.
+pub async fn poll(
+    seen: &Seen,
+    concurrency: usize,
+    archive: bool,
+) -> Result<Vec<Report>, FeedError> {
+    let mut reports = vec![];
+
+    for subscription in subscriptions(seen).await? {
+        let feed = match subscription
+            .url
+            .parse::<Uri>()
+            .map_err(|e| FeedError::Invalid(e.to_string()))
+        {
+            Ok(url) => fetch_feed(seen, &url).await,
+            Err(e) => Err(e),
+        };
+
+        let feed = match feed {
+            Ok(feed) => feed,
+            Err(e) => {
+                reports.push(Report {
+                    target: subscription.url,
+                    status: Status::Failed(batch::describe(&e)),
+                });
+                continue;
+            }
+        };
+
+        let known = sqlx::query!(
+            "SELECT guid FROM feed_entries WHERE feed = ?",
+            subscription.id
+        )
+        .fetch_all(&seen.pool)
+        .await?
+        .into_iter()
+        .map(|r| r.guid)
+        .collect::<HashSet<_>>();
+
+        let new = feed
+            .entries
+            .into_iter()
+            .filter(|e| !known.contains(&e.id))
+            .collect::<Vec<_>>();
+
+        let batch = new
+            .iter()
+            .map(|e| Entry {
+                target: e.url.clone(),
+                tags: vec![],
+                time: None,
+            })
+            .collect();
+
+        let feed_reports =
+            batch::add_all(seen, batch, &subscription.tags, concurrency, archive, false).await;
+
+        let now = Local::now();
+
+        for (entry, report) in new.iter().zip(&feed_reports) {
+            if matches!(report.status, Status::Failed(_)) {
+                continue;
+            }
+
+            sqlx::query!(
+                "INSERT OR IGNORE INTO feed_entries (feed, guid, url, ingested) VALUES (?, ?, ?, ?)",
+                subscription.id,
+                entry.id,
+                entry.url,
+                now
+            )
+            .execute(&seen.pool)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE feeds SET title = ?, last_polled = ? WHERE id = ?",
+            feed.title,
+            now,
+            subscription.id
+        )
+        .execute(&seen.pool)
+        .await?;
+
+        reports.extend(feed_reports);
+    }
+
+    Ok(reports)
+}
+
+/// Download and parse feed at `url`. It is downloaded like sources (see
+/// [`job::fetch`]), respecting robots.txt, hosts and preferences for `url`.
+pub async fn fetch_feed(seen: &Seen, url: &Uri) -> Result<Feed, FeedError> {
+    let (_, _, body) = job::fetch(seen, url).await.map_err(Box::new)?;
+    parse_feed(&String::from_utf8_lossy(&body), url)
+}
+
+/// Parse RSS (2.0 or 1.0) or Atom feed. Relative URLs of entries are resolved
+/// against `base`.
+pub fn parse_feed(xml: &str, base: &Uri) -> Result<Feed, FeedError> {
+    // Old RSS versions declare document type.
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = roxmltree::Document::parse_with_options(xml, options)
+        .map_err(|e| FeedError::Invalid(e.to_string()))?;
+    let root = document.root_element();
+    let base = url::Url::parse(&base.to_string()).ok();
+
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|c| c.tag_name().name() == name)
+            .and_then(|c| c.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+    };
+
+    let resolve = |link: String| match &base {
+        Some(base) => base.join(&link).map(|u| u.to_string()).unwrap_or(link),
+        None => link,
+    };
+
+    let (title, entries) = match root.tag_name().name() {
+        // RSS 2.0 has items inside channel, RSS 1.0 next to it.
+        "rss" | "RDF" => {
+            let channel = root
+                .children()
+                .find(|c| c.tag_name().name() == "channel")
+                .ok_or_else(|| FeedError::Invalid("missing channel".to_string()))?;
+
+            let items = channel
+                .children()
+                .chain(root.children())
+                .filter(|c| c.tag_name().name() == "item");
+
+            let entries = items
+                .filter_map(|item| {
+                    let url = resolve(child_text(item, "link")?);
+                    Some(FeedEntry {
+                        id: child_text(item, "guid").unwrap_or_else(|| url.clone()),
+                        url,
+                        title: child_text(item, "title"),
+                    })
+                })
+                .collect();
+
+            (child_text(channel, "title"), entries)
+        }
+        "feed" => {
+            let entries = root
+                .children()
+                .filter(|c| c.tag_name().name() == "entry")
+                .filter_map(|entry| {
+                    let link = entry
+                        .children()
+                        .filter(|c| c.tag_name().name() == "link")
+                        .find(|l| matches!(l.attribute("rel"), None | Some("alternate")))?
+                        .attribute("href")?;
+                    let url = resolve(link.to_string());
+                    Some(FeedEntry {
+                        id: child_text(entry, "id").unwrap_or_else(|| url.clone()),
+                        url,
+                        title: child_text(entry, "title"),
+                    })
+                })
+                .collect();
+
+            (child_text(root, "title"), entries)
+        }
+        other => return Err(FeedError::Invalid(format!("unexpected element {other}"))),
+    };
+
+    Ok(Feed { title, entries })
+}
+
+#[cfg(test)]
+mod test {
+    use isahc::http::StatusCode;
+
+    use super::{fetch_feed, parse_feed, FeedEntry, FeedError};
+    use crate::job::JobError;
+    use crate::testing::{response, serve};
+    use crate::Seen;
+
+    const RSS: &str = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>Blog</title>
+  <item><title>First</title><link>/posts/first</link><guid>post-1</guid></item>
+  <item><title>Second</title><link>https://example.com/posts/second</link></item>
+</channel></rss>"#;
+
+    #[test]
+    fn parse_atom() {
+        let atom = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Blog</title>
+  <entry>
+    <title>First</title>
+    <link rel="replies" href="/posts/first#comments"/>
+    <link href="/posts/first"/>
+    <id>tag:example.com,2023:1</id>
+  </entry>
+</feed>"#;
+
+        let feed = parse_feed(atom, &"https://example.com/feed.xml".parse().unwrap()).unwrap();
+
+        assert_eq!(feed.title.as_deref(), Some("Blog"));
+        assert_eq!(
+            feed.entries,
+            vec![FeedEntry {
+                id: "tag:example.com,2023:1".to_string(),
+                url: "https://example.com/posts/first".to_string(),
+                title: Some("First".to_string()),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn fetch_rss() {
+        let address = serve(|path| match path {
+            "/feed.xml" => response("200 OK", &[("Content-Type", "application/rss+xml")], RSS),
+            _ => response("404 Not Found", &[], ""),
+        })
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+        let url = format!("http://{address}/feed.xml").parse().unwrap();
+        let feed = fetch_feed(&seen, &url).await.unwrap();
+
+        assert_eq!(feed.entries.len(), 2);
+        assert_eq!(feed.entries[0].id, "post-1");
+        assert_eq!(feed.entries[0].url, format!("http://{address}/posts/first"));
+        assert_eq!(feed.entries[1].id, "https://example.com/posts/second");
+    }
+
+    #[tokio::test]
+    async fn fetch_missing_feed() {
+        let address = serve(|_| {
+            response(
+                "404 Not Found",
+                &[("Content-Type", "text/html")],
+                "<html><body>Not found</body></html>",
+            )
+        })
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+        let url = format!("http://{address}/feed.xml").parse().unwrap();
+        let error = fetch_feed(&seen, &url).await.unwrap_err();
+
+        assert!(matches!(
+            error,
+            FeedError::Download(e)
+                if matches!(*e, JobError::HttpStatus { status, .. } if status == StatusCode::NOT_FOUND)
+        ));
+    }
+}
@@ -41,9 +41,9 @@ pub fn time_fields(time: &DateTime<Local>) -> HashMap<String, Value> {
     };
 
     let season = match time.month() {
-        3 | 4 | 5 => "spring",
-        6 | 7 | 8 => "summer",
-        9 | 10 | 11 => "autumn",
+        3..=5 => "spring",
+        6..=8 => "summer",
+        9..=11 => "autumn",
         _ => "winter",
     };
 
@@ -0,0 +1,138 @@
+use chrono::{DateTime, Local, TimeZone};
+use kuchiki::traits::TendrilSink;
+use kuchiki::NodeRef;
+
+use crate::batch::Entry;
+
+/// Read bookmarks exported by a browser in Netscape bookmark file format.
+/// Names of folders in which a bookmark is placed become its tags, as well
+/// as tags given in its `TAGS` attribute. Time of the bookmark is taken
+/// from `ADD_DATE`. Only HTTP(S) bookmarks are read.
+pub fn parse(html: &str) -> Vec<Entry> {
+    let document = kuchiki::parse_html().one(html);
+
+    let links = match document.select("a[href]") {
+        Ok(links) => links,
+        Err(_) => return vec![],
+    };
+
+    links
+        .filter_map(|a| {
+            let attributes = a.attributes.borrow();
+            let href = attributes.get("href")?.trim();
+
+            if !(href.starts_with("http://") || href.starts_with("https://")) {
+                return None;
+            }
+
+            let mut tags = folders(a.as_node());
+
+            if let Some(t) = attributes.get("tags") {
+                tags.extend(
+                    t.split(',')
+                        .map(|t| t.trim())
+                        .filter(|t| !t.is_empty())
+                        .map(|t| t.to_string()),
+                );
+            }
+
+            tags.sort();
+            tags.dedup();
+
+            Some(Entry {
+                target: href.to_string(),
+                tags,
+                time: attributes.get("add_date").and_then(parse_timestamp),
+            })
+        })
+        .collect()
+}
+
+/// Names of folders containing `node`, outermost first. Folder is a `<DT>`
+/// with `<H3>` heading, followed by `<DL>` with its content, which the HTML
+/// parser places inside the `<DT>`. Toolbar and other special folders
+/// of browsers are left out.
+fn folders(node: &NodeRef) -> Vec<String> {
+    let mut folders = node
+        .ancestors()
+        .filter(|n| {
+            n.as_element()
+                .map(|e| &*e.name.local == "dt")
+                .unwrap_or(false)
+        })
+        .filter_map(|dt| {
+            let h3 = dt.children().find(|c| {
+                c.as_element()
+                    .map(|e| &*e.name.local == "h3")
+                    .unwrap_or(false)
+            })?;
+
+            let special = h3
+                .as_element()
+                .map(|e| {
+                    let attributes = e.attributes.borrow();
+                    attributes.contains("personal_toolbar_folder")
+                        || attributes.contains("unfiled_bookmarks_folder")
+                })
+                .unwrap_or(false);
+
+            let name = h3.text_contents().trim().to_string();
+
+            (!special && !name.is_empty()).then_some(name)
+        })
+        .collect::<Vec<_>>();
+
+    folders.reverse();
+    folders
+}
+
+/// Parse timestamp in seconds since the epoch. Some browsers use milliseconds
+/// or microseconds instead.
+fn parse_timestamp(s: &str) -> Option<DateTime<Local>> {
+    let mut ts: i64 = s.trim().parse().ok()?;
+
+    while ts > 100_000_000_000 {
+        ts /= 1000;
+    }
+
+    Local.timestamp_opt(ts, 0).single()
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use super::parse;
+
+    #[test]
+    fn parse_bookmarks() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1600000000" PERSONAL_TOOLBAR_FOLDER="true">Bookmarks bar</H3>
+    <DL><p>
+        <DT><H3 ADD_DATE="1600000000">Rust &amp; Co</H3>
+        <DL><p>
+            <DT><A HREF="https://www.rust-lang.org/" ADD_DATE="1650000000" TAGS="lang,Rust &amp; Co">Rust</A>
+            <DT><A HREF="place:sort=8">Recent</A>
+        </DL><p>
+        <DT><A HREF="https://example.com/" ADD_DATE="1660000000">Example</A>
+    </DL><p>
+</DL><p>
+"#;
+
+        let entries = parse(html);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].target, "https://www.rust-lang.org/");
+        assert_eq!(entries[0].tags, vec!["Rust & Co", "lang"]);
+        assert_eq!(
+            entries[0].time.unwrap(),
+            Utc.timestamp_opt(1650000000, 0).unwrap()
+        );
+        assert_eq!(entries[1].target, "https://example.com/");
+        assert!(entries[1].tags.is_empty());
+    }
+}
@@ -0,0 +1,244 @@
+use std::path::Path;
+
+use chrono::{DateTime, Local, TimeZone};
+use isahc::http::Uri;
+use miette::Diagnostic;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{Connection, SqliteConnection};
+use thiserror::Error;
+
+use crate::batch::Entry;
+use crate::url_preferences::{self, UrlPreferences};
+use crate::Seen;
+
+/// Microseconds between 1601-01-01 (beginning of Chromium time) and 1970-01-01.
+const CHROMIUM_EPOCH_OFFSET: i64 = 11_644_473_600_000_000;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum HistoryError {
+    #[error("Could not copy history database.")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not read history database.")]
+    Database(#[from] sqlx::Error),
+
+    #[error("File is neither Firefox (places.sqlite) nor Chromium (History) history.")]
+    UnknownFormat,
+}
+
+/// Visited page, as recorded by a browser.
+#[derive(Debug, PartialEq)]
+pub struct Visit {
+    pub url: String,
+    pub title: Option<String>,
+    /// How many times the page was visited.
+    pub visit_count: i64,
+    /// When the page was visited for the last time.
+    pub last_visit: DateTime<Local>,
+    /// How long the page was open in total, in seconds, if the browser knows.
+    pub duration: Option<i64>,
+}
+
+/// Which visited pages are worth importing.
+#[derive(Debug, Default)]
+pub struct HistoryFilter {
+    /// Pages visited fewer times are left out.
+    pub min_visits: i64,
+    /// Pages open for shorter time (in seconds) are left out. Only applies
+    /// if the browser records duration of visits (Chromium does, Firefox does not).
+    pub min_duration: Option<i64>,
+}
+
+impl HistoryFilter {
+    fn accepts(&self, visit: &Visit) -> bool {
+        let long_enough = match (self.min_duration, visit.duration) {
+            (Some(min), Some(duration)) => duration >= min,
+            _ => true,
+        };
+
+        visit.visit_count >= self.min_visits && long_enough
+    }
+}
+
+/// Read history database of Firefox or Chromium at `path` and select pages
+/// accepted by `filter` and not blacklisted. Pages are dated by their last visit.
+pub async fn import(
+    seen: &Seen,
+    path: &Path,
+    filter: &HistoryFilter,
+) -> Result<Vec<Entry>, HistoryError> {
+    let mut entries = vec![];
+
+    for visit in read_history(path).await? {
+        if !filter.accepts(&visit) {
+            continue;
+        }
+
+        let url: Uri = match visit.url.parse() {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+
+        if let Some(UrlPreferences::Blacklist) = url_preferences::for_url(&url, seen).await {
+            continue;
+        }
+
+        entries.push(Entry {
+            target: visit.url,
+            tags: vec![],
+            time: Some(visit.last_visit),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Read all HTTP(S) pages from history database of Firefox or Chromium. The database
+/// is copied first, since browsers keep it locked while running.
+pub async fn read_history(path: &Path) -> Result<Vec<Visit>, HistoryError> {
+    let copy = tempfile::NamedTempFile::new()?;
+    tokio::fs::copy(path, copy.path()).await?;
+
+    let mut conn = SqliteConnection::connect_with(
+        &SqliteConnectOptions::new()
+            .filename(copy.path())
+            .read_only(true),
+    )
+    .await?;
+
+    let tables: Vec<(String,)> =
+        sqlx::query_as("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .fetch_all(&mut conn)
+            .await?;
+
+    let has_table = |name: &str| tables.iter().any(|(t,)| t == name);
+
+    let visits = if has_table("moz_places") {
+        sqlx::query_as::<_, (String, Option<String>, i64, i64)>(
+            r#"
+SELECT url, title, visit_count, last_visit_date
+FROM moz_places
+WHERE hidden = 0 AND last_visit_date IS NOT NULL"#,
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|(url, title, visit_count, last_visit)| Visit {
+            url,
+            title,
+            visit_count,
+            last_visit: from_micros(last_visit),
+            duration: None,
+        })
+        .collect::<Vec<_>>()
+    } else if has_table("urls") && has_table("visits") {
+        sqlx::query_as::<_, (String, Option<String>, i64, i64, Option<i64>)>(
+            r#"
+SELECT urls.url, urls.title, urls.visit_count, urls.last_visit_time, SUM(visits.visit_duration)
+FROM urls
+LEFT JOIN visits ON visits.url = urls.id
+WHERE urls.hidden = 0
+GROUP BY urls.id"#,
+        )
+        .fetch_all(&mut conn)
+        .await?
+        .into_iter()
+        .map(|(url, title, visit_count, last_visit, duration)| Visit {
+            url,
+            title,
+            visit_count,
+            last_visit: from_micros(last_visit - CHROMIUM_EPOCH_OFFSET),
+            duration: duration.map(|d| d / 1_000_000),
+        })
+        .collect::<Vec<_>>()
+    } else {
+        return Err(HistoryError::UnknownFormat);
+    };
+
+    Ok(visits
+        .into_iter()
+        .filter(|v| v.url.starts_with("http://") || v.url.starts_with("https://"))
+        .collect())
+}
+
+/// Time given in microseconds since the epoch.
+fn from_micros(micros: i64) -> DateTime<Local> {
+    Local
+        .timestamp_opt(micros.div_euclid(1_000_000), 0)
+        .single()
+        .unwrap_or_else(Local::now)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use sqlx::{Connection, Executor, SqliteConnection};
+
+    use super::{read_history, HistoryFilter, CHROMIUM_EPOCH_OFFSET};
+
+    async fn fixture(schema: &str) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let url = format!("sqlite://{}", file.path().display());
+        let mut conn = SqliteConnection::connect(&url).await.unwrap();
+        conn.execute(schema).await.unwrap();
+        conn.close().await.unwrap();
+        file
+    }
+
+    #[tokio::test]
+    async fn read_firefox_history() {
+        let db = fixture(
+            r#"
+CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT, visit_count INTEGER,
+                         hidden INTEGER, last_visit_date INTEGER);
+INSERT INTO moz_places VALUES (1, 'https://example.com/', 'Example', 3, 0, 1650000000000000);
+INSERT INTO moz_places VALUES (2, 'place:sort=8', NULL, 1, 0, 1650000000000000);
+INSERT INTO moz_places VALUES (3, 'https://example.com/bookmarked', NULL, 0, 0, NULL);"#,
+        )
+        .await;
+
+        let visits = read_history(db.path()).await.unwrap();
+
+        assert_eq!(visits.len(), 1);
+        assert_eq!(visits[0].url, "https://example.com/");
+        assert_eq!(visits[0].visit_count, 3);
+        assert_eq!(
+            visits[0].last_visit,
+            Utc.timestamp_opt(1650000000, 0).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn read_chromium_history() {
+        let db = fixture(&format!(
+            r#"
+CREATE TABLE urls (id INTEGER PRIMARY KEY, url TEXT, title TEXT, visit_count INTEGER,
+                   last_visit_time INTEGER, hidden INTEGER);
+CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER,
+                     visit_duration INTEGER);
+INSERT INTO urls VALUES (1, 'https://example.com/long', 'Long', 2, {t}, 0);
+INSERT INTO urls VALUES (2, 'https://example.com/short', 'Short', 5, {t}, 0);
+INSERT INTO visits VALUES (1, 1, {t}, 90000000);
+INSERT INTO visits VALUES (2, 1, {t}, 30000000);
+INSERT INTO visits VALUES (3, 2, {t}, 1000000);"#,
+            t = 1650000000000000 + CHROMIUM_EPOCH_OFFSET
+        ))
+        .await;
+
+        let visits = read_history(db.path()).await.unwrap();
+
+        assert_eq!(visits.len(), 2);
+        assert_eq!(visits[0].duration, Some(120));
+        assert_eq!(
+            visits[0].last_visit,
+            Utc.timestamp_opt(1650000000, 0).unwrap()
+        );
+
+        let filter = HistoryFilter {
+            min_visits: 1,
+            min_duration: Some(60),
+        };
+        let accepted = visits.iter().filter(|v| filter.accepts(v)).count();
+        assert_eq!(accepted, 1);
+    }
+}
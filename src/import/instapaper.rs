@@ -0,0 +1,82 @@
+use chrono::{Local, TimeZone};
+use serde::Deserialize;
+
+use super::{ImportError, ARCHIVED_TAG};
+use crate::batch::Entry;
+
+/// One row of Instapaper CSV export.
+#[derive(Debug, Deserialize)]
+struct Row {
+    #[serde(rename = "URL")]
+    url: String,
+    /// "Unread", "Archive", "Starred" or name of user's folder.
+    #[serde(rename = "Folder", default)]
+    folder: String,
+    /// Time of saving in seconds since the epoch.
+    #[serde(rename = "Timestamp", default)]
+    timestamp: Option<i64>,
+    /// Present only in newer exports, as JSON array.
+    #[serde(rename = "Tags", default)]
+    tags: Option<String>,
+}
+
+/// Read CSV export of Instapaper. Folder of an article becomes its tag,
+/// archived articles get tag [`ARCHIVED_TAG`]. Rows with URLs other than
+/// HTTP(S) are skipped.
+pub fn parse(csv: &str) -> Result<Vec<Entry>, ImportError> {
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+
+    let mut entries = vec![];
+
+    for row in reader.deserialize() {
+        let row: Row = row?;
+
+        if !(row.url.starts_with("http://") || row.url.starts_with("https://")) {
+            continue;
+        }
+
+        let mut tags = row
+            .tags
+            .and_then(|t| serde_json::from_str::<Vec<String>>(&t).ok())
+            .unwrap_or_default();
+
+        match row.folder.as_str() {
+            "" | "Unread" => (),
+            "Archive" => tags.push(ARCHIVED_TAG.to_string()),
+            folder => tags.push(folder.to_lowercase()),
+        }
+
+        entries.push(Entry {
+            target: row.url,
+            tags,
+            time: row
+                .timestamp
+                .and_then(|t| Local.timestamp_opt(t, 0).single()),
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn parse_instapaper_export() {
+        let csv = "URL,Title,Selection,Folder,Timestamp,Tags\n\
+                   https://example.com/a,\"A, and more\",,Unread,1650000000,\"[\"\"rust\"\"]\"\n\
+                   https://example.com/b,B,,Archive,1660000000,[]\n\
+                   instapaper-private://email/1,Newsletter,,Unread,1660000000,\n\
+                   https://example.com/c,C,,Recipes,,\n";
+
+        let entries = parse(csv).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].tags, vec!["rust"]);
+        assert_eq!(entries[0].time.unwrap().timestamp(), 1650000000);
+        assert_eq!(entries[1].tags, vec!["archived"]);
+        assert_eq!(entries[2].tags, vec!["recipes"]);
+        assert!(entries[2].time.is_none());
+    }
+}
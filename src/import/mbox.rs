@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use chrono::Local;
+use isahc::http::Uri;
+
+use crate::batch::{describe, Report, Status};
+use crate::job::{existing_document, index_source};
+use crate::local::path_to_uri;
+use crate::source::{make_email, Email, Source};
+use crate::url_preferences::{self, Preferences, UrlPreferences};
+use crate::Seen;
+
+use super::ImportError;
+
+/// Index every message of mbox archive at `path` (or of a single .eml file),
+/// dated by when it was sent. Messages are identified by `mid:` URLs made
+/// of their Message-ID, so that the same message found in several archives
+/// is indexed only once.
+pub async fn import(
+    seen: &Seen,
+    path: &Path,
+    tags: &[String],
+    dry_run: bool,
+) -> Result<Vec<Report>, ImportError> {
+    let data = tokio::fs::read(path).await?;
+    let archive_url = path_to_uri(&path.canonicalize()?)?;
+
+    let mut reports = vec![];
+    let mut seen_urls = HashSet::new();
+
+    for (i, message) in split_mbox(&data).into_iter().enumerate() {
+        // Position within the archive identifies messages without Message-ID.
+        let url = format!("{archive_url}?message={}", i + 1)
+            .parse::<Uri>()
+            .unwrap_or_else(|_| archive_url.clone());
+
+        let status = match make_email(url.clone(), &message) {
+            Err(e) => Status::Failed(describe(&e)),
+            Ok(mut email) => {
+                if let Some(mid) = email.message_id.as_deref().and_then(message_uri) {
+                    email.url = mid;
+                }
+
+                if !seen_urls.insert(email.url.to_string()) {
+                    Status::Skipped("repeated in archive".to_string())
+                } else {
+                    add_email(seen, email, tags, dry_run).await
+                }
+            }
+        };
+
+        reports.push(Report {
+            target: url.to_string(),
+            status,
+        });
+    }
+
+    Ok(reports)
+}
+
+async fn add_email(seen: &Seen, email: Email, tags: &[String], dry_run: bool) -> Status {
+    let url = email.url.clone();
+
+    let preferences = match url_preferences::for_url(&url, seen).await {
+        Some(UrlPreferences::Blacklist) => return Status::Skipped("blacklisted".to_string()),
+        Some(UrlPreferences::Preferences(p)) => p,
+        None => Preferences::default(),
+    };
+
+    match existing_document(seen, &url).await {
+        Ok(Some(_)) => return Status::Skipped("already indexed".to_string()),
+        Ok(None) => (),
+        Err(e) => return Status::Failed(describe(&e)),
+    }
+
+    if dry_run {
+        return Status::Added(None);
+    }
+
+    let time = email.date.unwrap_or_else(Local::now);
+
+    let metadata = HashMap::from([("tag".to_string(), serde_json::to_value(tags).unwrap())]);
+
+    match index_source(
+        seen,
+        &url,
+        &[],
+        Source::Email(email),
+        &preferences,
+        metadata,
+        time,
+        tags,
+    )
+    .await
+    {
+        Ok(uuid) => Status::Added(Some(uuid)),
+        Err(e) => Status::Failed(describe(&e)),
+    }
+}
+
+/// `mid:` URL (RFC 2392) of message with `message_id` (with or without angle brackets).
+fn message_uri(message_id: &str) -> Option<Uri> {
+    let id = message_id
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    let (local, domain) = id.rsplit_once('@')?;
+
+    let encode = |s: &str| {
+        s.bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            })
+            .collect::<String>()
+    };
+
+    format!("mid:{}@{}", encode(local), encode(domain))
+        .parse()
+        .ok()
+}
+
+/// Split mbox archive into individual messages. Messages start with a line
+/// beginning with `From `, such lines within messages are quoted by `>`,
+/// which is removed (mboxrd). Content which does not start with `From `
+/// line is a single message, as in .eml files.
+fn split_mbox(data: &[u8]) -> Vec<Vec<u8>> {
+    if !data.starts_with(b"From ") {
+        return vec![data.to_vec()];
+    }
+
+    let mut messages = vec![];
+    let mut current: Option<Vec<u8>> = None;
+
+    for line in data.split_inclusive(|b| *b == b'\n') {
+        if line.starts_with(b"From ") {
+            messages.extend(current.take());
+            current = Some(vec![]);
+            continue;
+        }
+
+        let quotes = line.iter().take_while(|b| **b == b'>').count();
+        let line = if quotes > 0 && line[quotes..].starts_with(b"From ") {
+            &line[1..]
+        } else {
+            line
+        };
+
+        current.get_or_insert_with(Vec::new).extend_from_slice(line);
+    }
+
+    messages.extend(current);
+    messages.retain(|m| !m.iter().all(u8::is_ascii_whitespace));
+    messages
+}
+
+#[cfg(test)]
+mod test {
+    use super::{message_uri, split_mbox};
+
+    #[test]
+    fn split_messages() {
+        let mbox = b"From news@example.com Tue Mar 14 10:00:00 2023\n\
+Subject: First\n\
+\n\
+>From the editor.\n\
+\n\
+From news@example.com Tue Mar 21 10:00:00 2023\n\
+Subject: Second\n\
+\n\
+>>From quoted twice.\n";
+
+        let messages = split_mbox(mbox);
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            String::from_utf8_lossy(&messages[0]),
+            "Subject: First\n\nFrom the editor.\n\n"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&messages[1]),
+            "Subject: Second\n\n>From quoted twice.\n"
+        );
+
+        assert_eq!(split_mbox(b"Subject: Single\n\nBody\n").len(), 1);
+    }
+
+    #[test]
+    fn mid_url() {
+        assert_eq!(
+            message_uri("<CAF+x/y=1@mail.example.com>")
+                .unwrap()
+                .to_string(),
+            "mid:CAF%2Bx%2Fy%3D1@mail.example.com"
+        );
+        assert!(message_uri("no-domain").is_none());
+    }
+}
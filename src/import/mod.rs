@@ -0,0 +1,30 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::local::LocalError;
+
+pub mod bookmarks;
+pub mod history;
+pub mod instapaper;
+pub mod mbox;
+pub mod pinboard;
+pub mod pocket;
+pub mod warc;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ImportError {
+    #[error("Could not read CSV export.")]
+    Csv(#[from] csv::Error),
+
+    #[error("Could not read JSON export.")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Could not read file.")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not read local file.")]
+    Local(#[from] LocalError),
+}
+
+/// Tag given to sources which were archived (marked as read) in a read-later service.
+pub const ARCHIVED_TAG: &str = "archived";
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+
+use super::ImportError;
+use crate::batch::Entry;
+
+/// Tag given to bookmarks marked as "to read" in Pinboard.
+const TO_READ_TAG: &str = "toread";
+
+/// One bookmark of Pinboard JSON export.
+#[derive(Debug, Deserialize)]
+struct Bookmark {
+    href: String,
+    /// Time of saving, e. g. `2020-01-01T12:00:00Z`.
+    time: Option<DateTime<Local>>,
+    /// Tags separated by spaces.
+    #[serde(default)]
+    tags: String,
+    /// "yes" or "no".
+    #[serde(default)]
+    toread: String,
+}
+
+/// Read JSON export of Pinboard. Bookmarks marked as "to read" get tag `toread`,
+/// bookmarks with URLs other than HTTP(S) are skipped.
+pub fn parse(json: &str) -> Result<Vec<Entry>, ImportError> {
+    let bookmarks: Vec<Bookmark> = serde_json::from_str(json)?;
+
+    Ok(bookmarks
+        .into_iter()
+        .filter(|b| b.href.starts_with("http://") || b.href.starts_with("https://"))
+        .map(|b| {
+            let mut tags: Vec<String> = b.tags.split_whitespace().map(|t| t.to_string()).collect();
+
+            if b.toread == "yes" {
+                tags.push(TO_READ_TAG.to_string());
+            }
+
+            Entry {
+                target: b.href,
+                tags,
+                time: b.time,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn parse_pinboard_export() {
+        let json = r#"[
+{"href":"https://example.com/a","description":"A","extended":"","meta":"x","hash":"y",
+ "time":"2022-04-15T05:20:00Z","shared":"no","toread":"yes","tags":"rust async"},
+{"href":"javascript:void(0)","description":"Bookmarklet","time":"2022-05-01T00:00:00Z","tags":""},
+{"href":"https://example.com/b","description":"B","time":"2022-08-08T22:13:20Z","tags":""}
+]"#;
+
+        let entries = parse(json).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tags, vec!["rust", "async", "toread"]);
+        assert_eq!(entries[0].time.unwrap().timestamp(), 1650000000);
+        assert!(entries[1].tags.is_empty());
+    }
+}
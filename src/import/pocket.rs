@@ -0,0 +1,96 @@
+use chrono::{Local, TimeZone};
+use kuchiki::traits::TendrilSink;
+
+use super::ARCHIVED_TAG;
+use crate::batch::Entry;
+
+/// Read HTML export of Pocket. Articles are listed under headings "Unread"
+/// and "Read Archive"; the archived ones get tag [`ARCHIVED_TAG`]. Time
+/// of saving is taken from `time_added`. Links other than HTTP(S) are skipped.
+pub fn parse(html: &str) -> Vec<Entry> {
+    let document = kuchiki::parse_html().one(html);
+
+    let mut archived = false;
+    let mut entries = vec![];
+
+    for node in document.descendants() {
+        let element = match node.as_element() {
+            Some(element) => element,
+            None => continue,
+        };
+
+        match &*element.name.local {
+            "h1" => archived = node.text_contents().to_lowercase().contains("archive"),
+            "a" => {
+                let attributes = element.attributes.borrow();
+
+                let href = match attributes.get("href") {
+                    Some(href) => href.trim().to_string(),
+                    None => continue,
+                };
+
+                if !(href.starts_with("http://") || href.starts_with("https://")) {
+                    continue;
+                }
+
+                let mut tags = attributes
+                    .get("tags")
+                    .map(|t| {
+                        t.split(',')
+                            .map(|t| t.trim())
+                            .filter(|t| !t.is_empty())
+                            .map(|t| t.to_string())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+
+                if archived {
+                    tags.push(ARCHIVED_TAG.to_string());
+                }
+
+                let time = attributes
+                    .get("time_added")
+                    .and_then(|t| t.trim().parse().ok())
+                    .and_then(|t| Local.timestamp_opt(t, 0).single());
+
+                entries.push(Entry {
+                    target: href,
+                    tags,
+                    time,
+                });
+            }
+            _ => (),
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn parse_pocket_export() {
+        let html = r#"<!DOCTYPE html>
+<html><head><title>Pocket Export</title></head><body>
+<h1>Unread</h1>
+<ul>
+<li><a href="https://example.com/a" time_added="1650000000" tags="rust,async">A</a></li>
+<li><a href="file:///home/user/notes.html" time_added="1650000000">Notes</a></li>
+</ul>
+<h1>Read Archive</h1>
+<ul>
+<li><a href="https://example.com/b" time_added="1660000000" tags="">B</a></li>
+</ul>
+</body></html>"#;
+
+        let entries = parse(html);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tags, vec!["rust", "async"]);
+        assert_eq!(entries[0].time.unwrap().timestamp(), 1650000000);
+        assert_eq!(entries[1].target, "https://example.com/b");
+        assert_eq!(entries[1].tags, vec!["archived"]);
+    }
+}
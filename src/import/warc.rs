@@ -0,0 +1,166 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::Path;
+
+use chrono::Local;
+use isahc::http::header::CONTENT_TYPE;
+use isahc::http::Uri;
+use mime::Mime;
+
+use crate::batch::{describe, Report, Status};
+use crate::job::index_source;
+use crate::source::{make_page, Source, SourceType};
+use crate::url_preferences::{self, Preferences, UrlPreferences};
+use crate::warc::{parse_response, WarcError, WarcReader};
+use crate::Seen;
+
+/// Index every successfully captured HTML page from WARC file at `path`,
+/// dated by its capture. Nothing is downloaded and nothing is archived, the WARC
+/// file itself is the archive. If a page was captured more than once, only
+/// its first capture is indexed. Invalid records are reported and skipped.
+pub async fn import(
+    seen: &Seen,
+    path: &Path,
+    tags: &[String],
+    dry_run: bool,
+) -> Result<Vec<Report>, WarcError> {
+    let mut reports = vec![];
+    let mut seen_urls = HashSet::new();
+
+    for (i, record) in WarcReader::new(File::open(path)?).enumerate() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let io = matches!(e, WarcError::Io(_));
+                reports.push(Report {
+                    target: format!("record {}", i + 1),
+                    status: Status::Failed(describe(&e)),
+                });
+                // Invalid record is skipped, but nothing more can be read
+                // from a broken file.
+                if io {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        if record.header("WARC-Type") != Some("response") {
+            continue;
+        }
+
+        let target = match record.header("WARC-Target-URI") {
+            // Some tools enclose the URI in angle brackets.
+            Some(target) => target.trim_matches(|c| c == '<' || c == '>').to_string(),
+            None => continue,
+        };
+
+        let response = match parse_response(&record.block) {
+            Some(response) => response,
+            None => continue,
+        };
+
+        let is_page = response
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .and_then(|ct| ct.parse::<Mime>().ok())
+            .and_then(|ct| SourceType::from_mime(&ct))
+            .map(|t| matches!(t, SourceType::Page))
+            .unwrap_or(false);
+
+        if !is_page {
+            continue;
+        }
+
+        let status = if !response.status.is_success() {
+            Status::Skipped(format!("captured with status {}", response.status))
+        } else if !seen_urls.insert(target.clone()) {
+            Status::Skipped("captured repeatedly".to_string())
+        } else {
+            match target.parse::<Uri>() {
+                Err(e) => Status::Failed(describe(&e)),
+                Ok(url) => match url_preferences::for_url(&url, seen).await {
+                    Some(UrlPreferences::Blacklist) => Status::Skipped("blacklisted".to_string()),
+                    _ if dry_run => Status::Added(None),
+                    preferences => {
+                        let preferences = match preferences {
+                            Some(UrlPreferences::Preferences(p)) => p,
+                            _ => Preferences::default(),
+                        };
+
+                        let metadata = HashMap::from([(
+                            "tag".to_string(),
+                            serde_json::to_value(tags).unwrap(),
+                        )]);
+
+                        let source =
+                            Source::Page(make_page(url.clone(), response.headers, response.body));
+
+                        let time = record.date().unwrap_or_else(Local::now);
+
+                        match index_source(
+                            seen,
+                            &url,
+                            &[],
+                            source,
+                            &preferences,
+                            metadata,
+                            time,
+                            tags,
+                        )
+                        .await
+                        {
+                            Ok(uuid) => Status::Added(Some(uuid)),
+                            Err(e) => Status::Failed(describe(&e)),
+                        }
+                    }
+                },
+            }
+        };
+
+        reports.push(Report { target, status });
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod test {
+    use super::import;
+    use crate::batch::Status;
+    use crate::Seen;
+
+    fn response(uri: &str, html: &str) -> String {
+        let block = format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n{html}");
+        format!(
+            "WARC/1.0\r\nWARC-Type: response\r\nWARC-Target-URI: {uri}\r\n\
+             WARC-Date: 2023-01-15T10:00:00Z\r\nContent-Length: {}\r\n\r\n{block}\r\n\r\n",
+            block.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn untitled_and_invalid_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+
+        let path = dir.path().join("capture.warc");
+        let warc = [
+            response("https://example.com/notes/page.html", "<p>Just text.</p>"),
+            response("http://[invalid", "<title>Invalid</title>"),
+        ]
+        .concat();
+        std::fs::write(&path, warc).unwrap();
+
+        let reports = import(&seen, &path, &[], false).await.unwrap();
+
+        assert_eq!(reports.len(), 2);
+        let uuid = match &reports[0].status {
+            Status::Added(Some(uuid)) => *uuid,
+            status => panic!("unexpected status {status:?}"),
+        };
+        assert_eq!(seen.get(&uuid).await.unwrap().title, "page.html");
+        assert!(matches!(reports[1].status, Status::Failed(_)));
+    }
+}
@@ -91,7 +91,7 @@ impl SeenIndex {
         let meta = serde_json::to_value(&document.metadata)
             .ok()
             .and_then(|j| j.as_object().cloned())
-            .unwrap_or_else(serde_json::Map::new);
+            .unwrap_or_default();
 
         doc.add_text(self.fields.title, &document.title);
         doc.add_text(self.fields.content, document.content.plain_text());
@@ -128,6 +128,7 @@ impl SeenIndex {
 
                 Ok(SearchHit {
                     score,
+                    fragment: snippet.fragment().to_string(),
                     snippet: Self::highlight(snippet),
                     title: doc
                         .get_first(self.fields.title)
@@ -219,5 +220,8 @@ pub struct SearchHit {
     pub score: Score,
     pub title: String,
     pub uuid: Uuid,
+    /// Snippet with highlighted matches.
     pub snippet: String,
+    /// Snippet without highlighting, as it appears in the content.
+    pub fragment: String,
 }
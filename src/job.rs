@@ -1,12 +1,15 @@
 use std::collections::HashMap;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, Utc};
+use futures::AsyncReadExt;
 use indicatif::*;
-use isahc::http::header::CONTENT_TYPE;
-use isahc::http::Uri;
-use isahc::{Metrics, Response, ResponseExt};
+use isahc::http::header::{CONTENT_TYPE, LOCATION, RETRY_AFTER};
+use isahc::http::{HeaderMap, HeaderValue, StatusCode, Uri};
+use isahc::prelude::*;
+use isahc::Metrics;
 use miette::Diagnostic;
-use mime::Mime;
+use mime::{Mime, HTML, TEXT, VIDEO};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use thiserror::Error;
 use tokio::sync::oneshot;
@@ -16,12 +19,81 @@ use uuid::Uuid;
 use crate::archive::archive_source;
 use crate::document::{Content, Prepare};
 use crate::index::IndexError;
+use crate::local::{self, LocalError};
 use crate::metadata::Metadata;
-use crate::source::{make_page, Source, SourceType};
-use crate::url_preferences::{self, Preferences, UrlPreferences};
-use crate::{Seen, SeenError};
+use crate::politeness;
+use crate::render::{self, RenderError};
+use crate::request::{self, RequestError};
+use crate::source::video::make_video;
+pub use crate::source::video::{format_timestamp, TranscriptLocation};
+use crate::source::{
+    is_notebook_url, make_email, make_epub, make_image, make_notebook, make_page, make_pdf,
+    make_text, EmailError, EpubError, ImageError, PdfError, Source, SourceType, VideoError,
+};
+use crate::url_preferences::{self, DownloadMode, Preferences, UrlPreferences};
+use crate::{ContentType, Seen, SeenError};
+
+/// Request to add a source, which can be queued and carried out later
+/// by a worker (see [`crate::queue`]).
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Job {
+    /// URL of the source.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+    /// Tags of the source.
+    pub tags: Vec<String>,
+    /// Additional information about the source.
+    pub hints: SourceHints,
+    /// Whether to archive the source.
+    pub archive: bool,
+    /// When the source was seen, if not now (e. g. when it was bookmarked).
+    #[serde(default)]
+    pub time: Option<DateTime<Local>>,
+}
+
+/// Additional information about a source, which cannot be obtained
+/// from its URL alone.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct SourceHints {
+    /// Transcript of a video. If present, the URL is considered to be a video,
+    /// regardless of its content type.
+    pub transcript: Option<TranscriptLocation>,
+    /// Page on which an image was found. Alternative text of the image is taken from it.
+    #[serde(with = "option_uri_serde")]
+    pub referrer: Option<Uri>,
+    /// HTML of the page already obtained elsewhere (e. g. rendered by a browser).
+    /// If present, the URL is not downloaded at all. It is not kept in queued jobs.
+    #[serde(skip)]
+    pub html: Option<Vec<u8>>,
+    /// Headers making the download conditional (`If-None-Match`, `If-Modified-Since`).
+    /// It is not kept in queued jobs.
+    #[serde(skip)]
+    pub conditional: HeaderMap,
+}
+
+mod option_uri_serde {
+    use isahc::http::Uri;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(uri: &Option<Uri>, s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match uri {
+            Some(uri) => s.serialize_some(&uri.to_string()),
+            None => s.serialize_none(),
+        }
+    }
 
-pub struct Job;
+    pub fn deserialize<'de, D>(d: D) -> Result<Option<Uri>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(d)?
+            .map(|s| s.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
 
 #[derive(Debug, Diagnostic, Error)]
 pub enum JobError {
@@ -34,9 +106,63 @@ pub enum JobError {
     #[error("")]
     InvalidResponse,
 
+    #[error("Content has not been modified.")]
+    NotModified,
+
+    #[error("{url} returned {status}.")]
+    HttpStatus { status: StatusCode, url: Uri },
+
+    #[error("Too many redirects from {0}.")]
+    TooManyRedirects(Uri),
+
+    #[error("Invalid redirect to {0:?}.")]
+    InvalidRedirect(String),
+
+    #[error("{0} is disallowed by robots.txt.")]
+    DisallowedByRobots(Uri),
+
+    #[error("{url} asks to wait {retry_after:?} before trying again.")]
+    RateLimited { url: Uri, retry_after: Duration },
+
+    #[error("Response is larger than {0} bytes.")]
+    TooLarge(u64),
+
+    #[error("Could not prepare request.")]
+    RequestError(#[from] RequestError),
+
     #[error("Adress was blacklisted")]
     Blacklisted,
 
+    #[error("Could not read response.")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not read local file.")]
+    LocalError(#[from] LocalError),
+
+    #[error("Could not render page.")]
+    RenderError(#[from] Box<RenderError>),
+
+    #[error("Could not read PDF document.")]
+    PdfError(#[from] PdfError),
+
+    #[error("Could not read image.")]
+    ImageError(#[from] ImageError),
+
+    #[error("Could not read Jupyter notebook.")]
+    NotebookError(#[from] serde_json::Error),
+
+    #[error("Could not read email message.")]
+    EmailError(#[from] EmailError),
+
+    #[error("Could not read EPUB book.")]
+    EpubError(#[from] EpubError),
+
+    #[error("Could not obtain video transcript.")]
+    VideoError(#[from] VideoError),
+
+    #[error("Videos can only be indexed with a transcript (use --transcript).")]
+    TranscriptRequired,
+
     #[error("Index error.")]
     IndexError(#[from] IndexError),
 
@@ -47,15 +173,91 @@ pub enum JobError {
     SeenError(#[from] SeenError),
 }
 
+impl JobError {
+    /// Whether trying again later may help.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            JobError::HttpError(_) | JobError::Io(_) | JobError::RateLimited { .. } => true,
+            JobError::HttpStatus { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
+        }
+    }
+
+    /// How long the server asked to wait before trying again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            JobError::RateLimited { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+/// How many redirects are followed unless configured otherwise.
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// How many times a rate-limited request is tried again before giving up.
+const MAX_RATE_LIMIT_RETRIES: usize = 3;
+
+/// Longest wait for a rate-limited host before giving up, and longest delay
+/// between requests to one host.
+pub(crate) const MAX_RETRY_WAIT: Duration = Duration::from_secs(300);
+
+/// Wait for a rate-limited host that does not say how long to wait.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
 pub async fn go(
     seen: &Seen,
     url: Uri,
     tags: &[String],
+    hints: SourceHints,
     archive: bool,
     dry_run: bool,
 ) -> Result<(), JobError> {
-    let default_metadata =
-        HashMap::from([("tag".to_string(), serde_json::to_value(tags).unwrap())]);
+    let multi = MultiProgress::new();
+
+    let job = Job {
+        url,
+        tags: tags.to_vec(),
+        hints,
+        archive,
+        time: None,
+    };
+
+    let res = add(seen, job, dry_run, &multi).await;
+
+    multi.println("Done.").unwrap();
+    multi.clear().unwrap();
+
+    res.map(|_| ())
+}
+
+/// Download, archive and index source described by `job`, reporting progress
+/// to `multi`. Returns UUID of the indexed document, unless it is a dry run.
+pub(crate) async fn add(
+    seen: &Seen,
+    job: Job,
+    dry_run: bool,
+    multi: &MultiProgress,
+) -> Result<Option<Uuid>, JobError> {
+    let Job {
+        url,
+        tags,
+        hints,
+        archive,
+        time,
+    } = job;
+
+    let mut default_metadata =
+        HashMap::from([("tag".to_string(), serde_json::to_value(&tags).unwrap())]);
+
+    if let Some(path) = local::uri_to_path(&url) {
+        default_metadata.insert(
+            "path".to_string(),
+            serde_json::to_value(path.to_string_lossy()).unwrap(),
+        );
+    }
 
     // 1. Get preferences for URL (glob?)
     let url_preferences: Option<UrlPreferences> = url_preferences::for_url(&url, seen).await;
@@ -66,30 +268,47 @@ pub async fn go(
         None => Ok(Default::default()),
     }?;
 
-    let multi = MultiProgress::new();
     let sty = ProgressStyle::with_template("{bar:40.green/yellow} {pos:>7}/{len:7}").unwrap();
 
-    let total_pb = multi.add(ProgressBar::new(3));
+    // Messages are printed even if progress bars are not shown.
+    let progress_bar = |len| {
+        if seen.options.show_progress_bar {
+            multi.add(ProgressBar::new(len))
+        } else {
+            ProgressBar::hidden()
+        }
+    };
+
+    let total_pb = progress_bar(3);
     total_pb.set_style(sty.clone());
     total_pb.tick();
 
-    let download_pb = multi.add(ProgressBar::new(0));
+    let download_pb = progress_bar(0);
     download_pb.set_style(sty.clone());
 
-    let time = Local::now();
+    let time = time.unwrap_or_else(Local::now);
+
+    // Local files and given HTML are not downloaded at all.
+    let route = (hints.html.is_none() && url.scheme_str() != Some("file"))
+        .then(|| request::route(&seen.options, &preferences.request))
+        .transpose()?;
 
     total_pb.inc(1);
-    let source = download_source(seen, &url, &preferences, download_pb.clone()).await?;
+    let (source, redirects) =
+        download_source(seen, &url, &preferences, &hints, download_pb.clone()).await?;
     multi
-        .println(format!(
-            "Source download finished in {:?}.",
-            download_pb.elapsed()
-        ))
+        .println(match route {
+            Some(route) => format!(
+                "Source download finished in {:?} (via {route}).",
+                download_pb.elapsed()
+            ),
+            None => format!("Source download finished in {:?}.", download_pb.elapsed()),
+        })
         .unwrap();
     total_pb.finish_and_clear();
 
     if archive && !dry_run {
-        let archive_pb = multi.add(ProgressBar::new(100));
+        let archive_pb = progress_bar(100);
         archive_pb.set_style(sty.clone());
         archive_pb.set_position(0);
 
@@ -100,36 +319,32 @@ pub async fn go(
         multi.println("Archived.").unwrap();
     }
 
-    let res = if !dry_run {
-        let index_pb = multi.add(ProgressBar::new(100));
+    if !dry_run {
+        let index_pb = progress_bar(100);
         index_pb.set_style(sty);
         index_pb.set_position(0);
 
         // Index the source.
-        let res = index_source(
+        let uuid = index_source(
             seen,
             &url,
+            &redirects,
             source,
             &preferences,
             default_metadata,
             time,
-            tags,
+            &tags,
         )
-        .await;
+        .await?;
 
         index_pb.set_position(100);
         index_pb.finish_and_clear();
-        multi.println("Indexed.").unwrap();
+        multi.println(format!("Indexed as {uuid}.")).unwrap();
 
-        res
+        Ok(Some(uuid))
     } else {
-        Ok(())
-    };
-
-    multi.println("Done.").unwrap();
-    multi.clear().unwrap();
-
-    res
+        Ok(None)
+    }
 }
 
 /// Regularly checks given download metrics and updates progress bar accordingly.
@@ -146,19 +361,170 @@ async fn download_progress(m: Metrics, pb: ProgressBar) {
     }
 }
 
+/// Download source from `url`, or read it from local file if it is a `file:` URI,
+/// unless its HTML is given in `hints`. The content type decides the type of source,
+/// unless overriden by `preferences` or `hints`. Pages for which `preferences` ask
+/// for [`DownloadMode::Rendered`] are loaded in headless Chromium. Returns the source
+/// and URLs `url` redirected to, ending with the effective URL of the source (empty
+/// if there were no redirects).
 pub async fn download_source(
     seen: &Seen,
     url: &Uri,
     preferences: &Preferences,
+    hints: &SourceHints,
     progress_bar: ProgressBar,
-) -> Result<Source, JobError> {
-    let response = seen.http_client.get_async(url).await?;
-
-    // TODO: check status
-    let _status = response.status();
+) -> Result<(Source, Vec<Uri>), JobError> {
+    let (url, redirects, headers, body) = if let Some(html) = &hints.html {
+        progress_bar.finish_and_clear();
+        (url.clone(), vec![], html_headers(), html.clone())
+    } else if url.scheme_str() == Some("file") {
+        progress_bar.finish_and_clear();
+        let (headers, body) = local::read_file(url, |m| needs_body(hints, m)).await?;
+        (url.clone(), vec![], headers, body)
+    } else if preferences.download == DownloadMode::Rendered {
+        progress_bar.finish_and_clear();
+        render_page(seen, url, preferences).await?
+    } else {
+        download(seen, url, hints, preferences, progress_bar).await?
+    };
 
     let ct = preferences.content_type.clone();
-    let effective_ct = ct.unwrap_or(content_type(&response)?);
+    let overridden = ct.is_some();
+    let effective_ct = ct.unwrap_or(content_type(&headers)?);
+
+    let transcript = hints.transcript.as_ref();
+
+    let source_type = if transcript.is_some() {
+        Some(SourceType::Video)
+    } else if !overridden && is_notebook_url(&url) {
+        Some(SourceType::Notebook)
+    } else {
+        SourceType::from_mime(&effective_ct)
+    };
+
+    let source: Source = match source_type {
+        Some(SourceType::Page) => Source::Page(make_page(url, headers, body)),
+        Some(SourceType::Pdf) => make_pdf(url, headers, body).map(Source::Pdf)?,
+        Some(SourceType::Text) => Source::Text(make_text(url, headers, body)),
+        Some(SourceType::Email) => make_email(url, &body).map(Source::Email)?,
+        Some(SourceType::Epub) => make_epub(url, headers, body).map(Source::Epub)?,
+        Some(SourceType::Notebook) => make_notebook(url, &body).map(Source::Notebook)?,
+        Some(SourceType::Image) => make_image(
+            seen,
+            url,
+            headers,
+            body,
+            hints.referrer.as_ref(),
+            seen.options.ocr_command.as_deref(),
+        )
+        .await
+        .map(Source::Image)?,
+        Some(SourceType::Video) if transcript.is_none() && effective_ct.type_() == VIDEO => {
+            Err(JobError::TranscriptRequired)?
+        }
+        Some(SourceType::Video) => make_video(&seen.http_client, url, &headers, body, transcript)
+            .await
+            .map(Source::Video)?,
+        None => Err(JobError::MimeNotSupported(effective_ct))?,
+    };
+
+    Ok((source, redirects))
+}
+
+/// Headers pretending that HTML given by user was returned by a server.
+fn html_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    headers
+}
+
+/// Download content from `url` over HTTP with requests customised by `preferences`,
+/// following at most `max_redirects` (see [`SeenOptions`](crate::options::SeenOptions))
+/// redirects. Requests are scheduled so that hosts are not overloaded, and robots.txt
+/// is respected unless `preferences` say otherwise. Returns effective URL (after
+/// redirects), URLs redirected to (in order, ending with the effective URL), headers
+/// and body of the response.
+async fn download(
+    seen: &Seen,
+    url: &Uri,
+    hints: &SourceHints,
+    preferences: &Preferences,
+    progress_bar: ProgressBar,
+) -> Result<(Uri, Vec<Uri>, HeaderMap, Vec<u8>), JobError> {
+    let settings = &preferences.request;
+    let max_redirects = seen.options.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS);
+    let route = request::route(&seen.options, settings)?;
+    let proxy = request::proxy(&route, &seen.options)?;
+
+    let mut effective_url = url.clone();
+    let mut redirects = vec![];
+    let mut rate_limited = 0;
+
+    // Redirects are followed here rather than by the client, so that we
+    // learn every URL on the way.
+    let (mut response, _permit) = loop {
+        check_robots(seen, &effective_url, preferences).await?;
+
+        let permit = seen.politeness.acquire(&effective_url).await;
+
+        let mut request =
+            request::build(&effective_url, url.host(), settings, proxy.as_ref()).await?;
+        request.headers_mut().extend(hints.conditional.clone());
+        let response = seen.http_client.send_async(request).await?;
+
+        // Try again once the host allows, other requests to it wait as well.
+        if let Some(wait) = rate_limit(response.status(), response.headers()) {
+            if rate_limited < MAX_RATE_LIMIT_RETRIES && wait <= MAX_RETRY_WAIT {
+                rate_limited += 1;
+                seen.politeness.back_off(&effective_url, wait).await;
+                continue;
+            }
+            return Err(JobError::RateLimited {
+                url: effective_url,
+                retry_after: wait,
+            });
+        }
+
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .filter(|_| response.status().is_redirection());
+
+        let location = match location {
+            Some(location) => location.clone(),
+            None => break (response, permit),
+        };
+
+        if redirects.len() >= max_redirects {
+            return Err(JobError::TooManyRedirects(url.clone()));
+        }
+
+        effective_url = resolve(&effective_url, &location).ok_or_else(|| {
+            JobError::InvalidRedirect(String::from_utf8_lossy(location.as_bytes()).to_string())
+        })?;
+        redirects.push(effective_url.clone());
+    };
+
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Err(JobError::NotModified);
+    } else if !status.is_success() {
+        return Err(JobError::HttpStatus {
+            status,
+            url: effective_url,
+        });
+    }
+
+    let headers = response.headers().clone();
+
+    if let Some(max) = settings.max_body_size {
+        if response.body().len().filter(|len| *len > max).is_some() {
+            return Err(JobError::TooLarge(max));
+        }
+    }
 
     let (downloaded_signal, downloaded) = oneshot::channel::<()>();
 
@@ -173,34 +539,180 @@ pub async fn download_source(
         });
     }
 
-    let source: Source = match SourceType::from_mime(&effective_ct) {
-        Some(SourceType::Page) => make_page(response, downloaded_signal)
+    let body = if !needs_body(hints, content_type(&headers).ok().as_ref()) {
+        vec![]
+    } else if let Some(max) = settings.max_body_size {
+        // Length of the body is not always known in advance.
+        let mut body = vec![];
+        response
+            .body_mut()
+            .take(max + 1)
+            .read_to_end(&mut body)
+            .await?;
+        if body.len() as u64 > max {
+            return Err(JobError::TooLarge(max));
+        }
+        body
+    } else {
+        response.bytes().await?
+    };
+
+    let _ = downloaded_signal.send(());
+
+    Ok((effective_url, redirects, headers, body))
+}
+
+/// Download `url`, which is not a source itself (e. g. a feed), the same way
+/// as sources (see [`download`]) with preferences for `url`. Returns effective
+/// URL, headers and body of the response.
+pub(crate) async fn fetch(seen: &Seen, url: &Uri) -> Result<(Uri, HeaderMap, Vec<u8>), JobError> {
+    let preferences = match url_preferences::for_url(url, seen).await {
+        Some(UrlPreferences::Blacklist) => return Err(JobError::Blacklisted),
+        Some(UrlPreferences::Preferences(p)) => p,
+        None => Preferences::default(),
+    };
+
+    let (effective_url, _, headers, body) = download(
+        seen,
+        url,
+        &SourceHints::default(),
+        &preferences,
+        ProgressBar::hidden(),
+    )
+    .await?;
+
+    Ok((effective_url, headers, body))
+}
+
+/// Load page at `url` in headless Chromium with requests customised by
+/// `preferences`. Like [`download`], it waits for its turn at the host and
+/// respects robots.txt, of both `url` and the effective URL. Returns the
+/// same as [`download`].
+async fn render_page(
+    seen: &Seen,
+    url: &Uri,
+    preferences: &Preferences,
+) -> Result<(Uri, Vec<Uri>, HeaderMap, Vec<u8>), JobError> {
+    let settings = &preferences.request;
+    let route = request::route(&seen.options, settings)?;
+    let proxy = request::proxy(&route, &seen.options)?;
+
+    check_robots(seen, url, preferences).await?;
+
+    let rendered = {
+        let _permit = seen.politeness.acquire(url).await;
+        render::render(url, settings, proxy.as_ref())
             .await
-            .map(Source::Page)
-            .unwrap(),
-        Some(SourceType::Image) => todo!(),
-        Some(SourceType::Video) => todo!(),
-        None => Err(JobError::MimeNotSupported(effective_ct))?,
+            .map_err(Box::new)?
     };
 
-    Ok(source)
+    let effective_url = rendered.url;
+    let redirects = if &effective_url != url {
+        check_robots(seen, &effective_url, preferences).await?;
+        vec![effective_url.clone()]
+    } else {
+        vec![]
+    };
+
+    if let Some(status) = rendered.status.filter(|s| !s.is_success()) {
+        return Err(JobError::HttpStatus {
+            status,
+            url: effective_url,
+        });
+    }
+
+    Ok((
+        effective_url,
+        redirects,
+        html_headers(),
+        rendered.html.into_bytes(),
+    ))
 }
 
+/// Fail if robots.txt disallows `url`, unless `preferences` say to ignore it.
+async fn check_robots(seen: &Seen, url: &Uri, preferences: &Preferences) -> Result<(), JobError> {
+    if !preferences.ignore_robots
+        && !seen
+            .politeness
+            .robots(&seen.http_client, url)
+            .await
+            .is_allowed(url)
+    {
+        return Err(JobError::DisallowedByRobots(url.clone()));
+    }
+
+    Ok(())
+}
+
+/// How long to wait before trying again, if response with `status` and `headers`
+/// says that we make too many requests.
+fn rate_limit(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    let retry_after = headers
+        .get(RETRY_AFTER)
+        .and_then(|v| politeness::retry_after(v, Utc::now()));
+
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)),
+        StatusCode::SERVICE_UNAVAILABLE => retry_after,
+        _ => None,
+    }
+}
+
+/// Target of redirect to `location` (possibly relative) from `url`.
+fn resolve(url: &Uri, location: &HeaderValue) -> Option<Uri> {
+    let base = url::Url::parse(&url.to_string()).ok()?;
+    base.join(location.to_str().ok()?)
+        .ok()?
+        .as_str()
+        .parse()
+        .ok()
+}
+
+/// Content of a video is not needed when its transcript is given. Only a page
+/// with the video may give us something useful (its title).
+fn needs_body(hints: &SourceHints, mime: Option<&Mime>) -> bool {
+    hints.transcript.is_none()
+        || mime
+            .map(|m| m.type_() == TEXT && m.subtype() == HTML)
+            .unwrap_or(true)
+}
+
+/// Index `source` requested from `url`, which redirected to `redirects`.
+/// The document can be found by any of these URLs. A document already
+/// indexed from one of them is replaced, keeping its UUID.
+#[allow(clippy::too_many_arguments)]
 pub async fn index_source(
     seen: &Seen,
     url: &Uri,
+    redirects: &[Uri],
     source: Source,
     preferences: &Preferences,
     default_metadata: HashMap<String, Value>,
     time: DateTime<Local>,
     tags: &[String],
-) -> Result<(), JobError> {
+) -> Result<Uuid, JobError> {
     // We do not want to index the same URL if it already exists.
     // Therefore, let's first delete documents bound to this URL if they
     // already exist
-    delete_existing(seen, url).await?;
+    let mut replaced = None;
+    for u in std::iter::once(url).chain(redirects) {
+        replaced = replaced.or(delete_existing(seen, u).await?);
+    }
 
-    let document = source.prepare_document(default_metadata, &seen.options, preferences, time);
+    let mut default_metadata = default_metadata;
+    if !redirects.is_empty() {
+        let urls: Vec<String> = std::iter::once(url)
+            .chain(redirects)
+            .map(|u| u.to_string())
+            .collect();
+        default_metadata.insert("url".to_string(), serde_json::to_value(urls).unwrap());
+    }
+
+    let mut document = source.prepare_document(default_metadata, &seen.options, preferences, time);
+
+    if let Some(uuid) = replaced {
+        document.uuid = uuid;
+    }
 
     let _ = seen.index.index(&document)?;
 
@@ -212,6 +724,7 @@ pub async fn index_source(
 
     let document_id: i64 = {
         let mjs = serde_json::to_string(&metadata).unwrap();
+        let content_type = ContentType::of(&document.content);
         sqlx::query!(
             "INSERT INTO documents (uuid, url, title, time, metadata, content_type) VALUES (?, ?, ?, ?, ?, ?)",
             document.uuid,
@@ -219,7 +732,7 @@ pub async fn index_source(
             document.title,
             document.time,
             mjs,
-	    "webpage"
+            content_type
         )
         .execute(&seen.pool)
         .await
@@ -227,6 +740,19 @@ pub async fn index_source(
         .last_insert_rowid()
     };
 
+    for (position, redirect) in redirects.iter().enumerate() {
+        let redirect_s = redirect.to_string();
+        let position = position as i64;
+        sqlx::query!(
+            "INSERT INTO redirects (url, position, document) VALUES (?, ?, ?)",
+            redirect_s,
+            position,
+            document_id
+        )
+        .execute(&seen.pool)
+        .await?;
+    }
+
     let q = match document.content {
         Content::WebPage { text, rich_text } => {
             sqlx::query!(
@@ -238,45 +764,218 @@ pub async fn index_source(
             .execute(&seen.pool)
             .await
         }
+        Content::Pdf { text, pages } => {
+            let pages = serde_json::to_string(&pages).unwrap();
+            sqlx::query!(
+                "INSERT INTO pdf (plain, pages, document) VALUES (?, ?, ?)",
+                text,
+                pages,
+                document_id
+            )
+            .execute(&seen.pool)
+            .await
+        }
+        Content::Book { text, chapters } => {
+            let chapters = serde_json::to_string(&chapters).unwrap();
+            sqlx::query!(
+                "INSERT INTO book (plain, chapters, document) VALUES (?, ?, ?)",
+                text,
+                chapters,
+                document_id
+            )
+            .execute(&seen.pool)
+            .await
+        }
+        Content::Image { text, mime, data } => {
+            sqlx::query!(
+                "INSERT INTO image (plain, mime, data, document) VALUES (?, ?, ?, ?)",
+                text,
+                mime,
+                data,
+                document_id
+            )
+            .execute(&seen.pool)
+            .await
+        }
+        Content::Text { text, rich_text } => {
+            sqlx::query!(
+                "INSERT INTO text (plain, rich, document) VALUES (?, ?, ?)",
+                text,
+                rich_text,
+                document_id
+            )
+            .execute(&seen.pool)
+            .await
+        }
+        Content::Video { text, cues } => {
+            let cues = serde_json::to_string(&cues).unwrap();
+            sqlx::query!(
+                "INSERT INTO video (plain, cues, document) VALUES (?, ?, ?)",
+                text,
+                cues,
+                document_id
+            )
+            .execute(&seen.pool)
+            .await
+        }
     };
 
-    println!("{q:?} — {:?}", document.uuid);
+    q?;
 
-    Ok(())
+    Ok(document.uuid)
+}
+
+/// Delete documents coming from `url` if they exist and return UUID of the
+/// deleted one. If no document exists, nothing happens.
+async fn delete_existing(seen: &Seen, url: &Uri) -> Result<Option<Uuid>, JobError> {
+    let existing = existing_document(seen, url).await?;
+
+    // If a document with the same URL already exists, we are updating it.
+    // Updating with tantivy equals to deleting + inserting again newly.
+    if let Some(uuid) = existing {
+        seen.delete(&uuid).await?;
+    }
+
+    Ok(existing)
 }
 
-/// Delete documents coming from `url` if they exist. If no document exists,
-/// nothing happens.
-async fn delete_existing(seen: &Seen, url: &Uri) -> Result<(), JobError> {
+/// UUID of document coming from `url`, if there is one. The document may
+/// have been requested from `url` or been redirected to it.
+pub(crate) async fn existing_document(seen: &Seen, url: &Uri) -> Result<Option<Uuid>, JobError> {
     let url_s = url.to_string();
 
     #[rustfmt::skip]
     let existing: Option<Uuid> =
         sqlx::query!(
-            r#"SELECT uuid AS "uuid: Uuid" FROM documents WHERE url = ?"#,
+            r#"SELECT uuid AS "uuid: Uuid" FROM documents WHERE url = ? OR id IN (SELECT document FROM redirects WHERE url = ?)"#,
+            url_s,
             url_s
         )
         .fetch_optional(&seen.pool)
         .await?
         .map(|r| r.uuid);
 
-    // If a document with the same URL already exists, we are updating it.
-    // Updating with tantivy equals to deleting + inserting again newly.
-    if let Some(uuid) = existing {
-        Ok(seen.delete(&uuid).await?)
-    } else {
-        Ok(())
-    }
+    Ok(existing)
 }
 
-/// Extract content type from given HTTP response.
-fn content_type<B>(response: &Response<B>) -> Result<Mime, JobError> {
-    let ct = response
-        .headers()
+/// Extract content type from given HTTP headers.
+fn content_type(headers: &HeaderMap) -> Result<Mime, JobError> {
+    let ct = headers
         .get(CONTENT_TYPE)
-        .unwrap()
+        .ok_or(JobError::InvalidResponse)?
         .to_str()
         .map_err(|_| JobError::InvalidResponse)?;
     let mime: Mime = ct.parse().map_err(|_| JobError::InvalidResponse)?;
     Ok(mime)
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::Local;
+    use isahc::http::{HeaderMap, HeaderValue, StatusCode, Uri};
+
+    use super::{download, index_source, resolve, JobError, SourceHints};
+    use crate::document::Content;
+    use crate::source::{make_text, Source};
+    use crate::testing::{response, serve};
+    use crate::url_preferences::Preferences;
+    use crate::Seen;
+
+    #[tokio::test]
+    async fn reindex_keeps_uuid() {
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+        let url: Uri = "https://example.com/notes.txt".parse().unwrap();
+        let preferences = Preferences::default();
+
+        let index = |text: &str| {
+            let source = Source::Text(make_text(url.clone(), HeaderMap::new(), text.into()));
+            index_source(
+                &seen,
+                &url,
+                &[],
+                source,
+                &preferences,
+                HashMap::new(),
+                Local::now(),
+                &[],
+            )
+        };
+
+        let first = index("first").await.unwrap();
+        let second = index("second").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(seen.list().await.unwrap().len(), 1);
+        assert!(matches!(
+            seen.get(&second).await.unwrap().content,
+            Content::Text { text, .. } if text == "second"
+        ));
+    }
+
+    #[test]
+    fn redirect_targets() {
+        let url: Uri = "https://example.com/a/b?c=d".parse().unwrap();
+        let target = |location: &'static str| {
+            resolve(&url, &HeaderValue::from_static(location)).map(|u| u.to_string())
+        };
+
+        assert_eq!(target("/x").as_deref(), Some("https://example.com/x"));
+        assert_eq!(target("y").as_deref(), Some("https://example.com/a/y"));
+        assert_eq!(
+            target("//other.org/z").as_deref(),
+            Some("https://other.org/z")
+        );
+        assert_eq!(
+            target("http://other.org/").as_deref(),
+            Some("http://other.org/")
+        );
+        assert_eq!(target("http://[invalid"), None);
+    }
+
+    #[tokio::test]
+    async fn follow_redirects() {
+        let address = serve(|path| match path {
+            "/old" => response("301 Moved Permanently", &[("Location", "/new")], ""),
+            "/new" => response("200 OK", &[("Content-Type", "text/plain")], "hello"),
+            _ => response("404 Not Found", &[], ""),
+        })
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+        let preferences = Preferences::default();
+        let hints = SourceHints::default();
+        let url = |path: &str| format!("http://{address}{path}").parse::<Uri>().unwrap();
+
+        let (effective_url, redirects, _, body) = download(
+            &seen,
+            &url("/old"),
+            &hints,
+            &preferences,
+            indicatif::ProgressBar::hidden(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(effective_url, url("/new"));
+        assert_eq!(redirects, vec![url("/new")]);
+        assert_eq!(body, b"hello");
+
+        let missing = download(
+            &seen,
+            &url("/missing"),
+            &hints,
+            &preferences,
+            indicatif::ProgressBar::hidden(),
+        )
+        .await;
+
+        assert!(matches!(
+            missing,
+            Err(JobError::HttpStatus { status: StatusCode::NOT_FOUND, url: u }) if u == url("/missing")
+        ));
+    }
+}
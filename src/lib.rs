@@ -1,16 +1,30 @@
 mod convert;
+pub mod crawl;
 pub mod document;
 //mod download;
 pub mod archive;
+pub mod batch;
 mod export;
 mod extract;
+pub mod feed;
+pub mod import;
 mod index;
 pub mod job;
+pub mod local;
 mod metadata;
 mod options;
+mod politeness;
+pub mod queue;
 mod readability;
+pub mod refresh;
+mod render;
+mod request;
+mod robots;
 mod source;
+#[cfg(test)]
+mod testing;
 mod url_preferences;
+mod warc;
 mod fields;
 
 use std::path::PathBuf;
@@ -20,6 +34,7 @@ use chrono::{DateTime, Local};
 use directories::ProjectDirs;
 use futures::{StreamExt, TryStreamExt};
 use index::IndexError;
+use isahc::config::RedirectPolicy;
 use isahc::prelude::Configurable;
 use isahc::HttpClient;
 use miette::{Diagnostic, Result};
@@ -32,6 +47,7 @@ use uuid::Uuid;
 
 use crate::document::{Content, Document};
 use crate::index::SeenIndex;
+use crate::politeness::Politeness;
 
 #[derive(Debug)]
 pub struct Seen {
@@ -45,6 +61,8 @@ pub struct Seen {
     dirs: ProjectDirs,
     /// Configuration options of the seesion.
     options: SeenOptions,
+    /// Scheduler of requests to hosts.
+    politeness: Politeness,
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -62,6 +80,21 @@ pub enum SeenError {
     Options(String),
 }
 
+/// HTTP client of a session, limited to configured number of redirects.
+fn http_client(options: &SeenOptions) -> Result<HttpClient, SeenError> {
+    let max_redirects = options.max_redirects.unwrap_or(job::DEFAULT_MAX_REDIRECTS);
+    // Requests made outside of downloads (feeds, robots.txt, …) take
+    // the default route as well.
+    let proxy = request::proxy(&request::default_route(options), options)
+        .map_err(|e| SeenError::Options(e.to_string()))?;
+
+    Ok(HttpClient::builder()
+        .metrics(true)
+        .redirect_policy(RedirectPolicy::Limit(max_redirects as u32))
+        .proxy(proxy)
+        .build()?)
+}
+
 impl Seen {
     /// Create new seen session with its database and full-text index.
     pub async fn new(_config: &Option<PathBuf>) -> Result<Seen, SeenError> {
@@ -69,12 +102,13 @@ impl Seen {
             SeenError::Options("Could not load directory for configuration files".to_string())
         })?;
 
-        let options = match read_to_string(dirs.config_dir().join("config.toml")).await {
+        let config = read_to_string(dirs.config_dir().join("config.toml")).await;
+        let options: SeenOptions = match config {
             Ok(s) => toml::from_str(&s).map_err(|e| SeenError::Options(e.to_string())),
             Err(_) => Ok(Default::default()),
         }?;
 
-        let http_client = HttpClient::builder().metrics(true).build()?;
+        let http_client = http_client(&options)?;
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
             .connect_with(
@@ -88,15 +122,54 @@ impl Seen {
 
         let index = Rc::new(SeenIndex::new(dirs.data_dir().join("index"))?);
 
+        let politeness = Politeness::new(&options);
+
         Ok(Seen {
             http_client,
             pool,
             index,
             dirs,
             options,
+            politeness,
         })
     }
 
+    /// Session with database and full-text index in `dir`, which does not
+    /// wait between requests to the same host.
+    #[cfg(test)]
+    pub(crate) async fn for_test(dir: &std::path::Path) -> Seen {
+        let options = SeenOptions {
+            host_delay: Some(0.0),
+            ..Default::default()
+        };
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect_with(
+                SqliteConnectOptions::new()
+                    .filename(dir.join("seen.db"))
+                    .create_if_missing(true),
+            )
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        Seen {
+            http_client: http_client(&options).unwrap(),
+            pool,
+            index: Rc::new(SeenIndex::new(dir.join("index")).unwrap()),
+            dirs: ProjectDirs::from("com.jirijakes", "", "Seen").unwrap(),
+            politeness: Politeness::new(&options),
+            options,
+        }
+    }
+
+    /// Whether sources asked to be added in background (i. e. queued) are
+    /// rather added right away, as configured.
+    pub fn always_in_foreground(&self) -> bool {
+        self.options.always_in_foreground
+    }
+
     /// Search among documents using a tantivy query.
     pub fn search(&self, query: &str) -> Result<Vec<index::SearchHit>, index::SearchError> {
         self.index.search(query)
@@ -104,7 +177,7 @@ impl Seen {
 
     /// Obtain content for given `partial_document` and return all as one [`Document`].
     async fn fill_content(&self, partial_document: PartialDocument) -> Result<Document, SeenError> {
-        match partial_document.content_type {
+        let content = match partial_document.content_type {
             ContentType::WebPage => {
                 let c = sqlx::query!(
                     r#"
@@ -117,19 +190,107 @@ WHERE documents.uuid = ?"#,
                 .fetch_one(&self.pool)
                 .await?;
 
-                Ok(Document {
-                    title: partial_document.title,
-                    url: partial_document.url.parse().unwrap(),
-                    uuid: partial_document.uuid,
-                    time: partial_document.time,
-                    content: Content::WebPage {
-                        text: c.plain,
-                        rich_text: c.rich,
-                    },
-                    metadata: serde_json::from_str(&partial_document.metadata).unwrap(),
-                })
+                Content::WebPage {
+                    text: c.plain,
+                    rich_text: c.rich,
+                }
             }
-        }
+            ContentType::Pdf => {
+                let c = sqlx::query!(
+                    r#"
+SELECT *
+FROM pdf
+LEFT JOIN documents ON pdf.document = documents.id
+WHERE documents.uuid = ?"#,
+                    partial_document.uuid
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Content::Pdf {
+                    text: c.plain,
+                    pages: serde_json::from_str(&c.pages).unwrap_or_default(),
+                }
+            }
+            ContentType::Book => {
+                let c = sqlx::query!(
+                    r#"
+SELECT *
+FROM book
+LEFT JOIN documents ON book.document = documents.id
+WHERE documents.uuid = ?"#,
+                    partial_document.uuid
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Content::Book {
+                    text: c.plain,
+                    chapters: serde_json::from_str(&c.chapters).unwrap_or_default(),
+                }
+            }
+            ContentType::Image => {
+                let c = sqlx::query!(
+                    r#"
+SELECT *
+FROM image
+LEFT JOIN documents ON image.document = documents.id
+WHERE documents.uuid = ?"#,
+                    partial_document.uuid
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Content::Image {
+                    text: c.plain,
+                    mime: c.mime,
+                    data: c.data,
+                }
+            }
+            ContentType::Text => {
+                let c = sqlx::query!(
+                    r#"
+SELECT *
+FROM text
+LEFT JOIN documents ON text.document = documents.id
+WHERE documents.uuid = ?"#,
+                    partial_document.uuid
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Content::Text {
+                    text: c.plain,
+                    rich_text: c.rich,
+                }
+            }
+            ContentType::Video => {
+                let c = sqlx::query!(
+                    r#"
+SELECT *
+FROM video
+LEFT JOIN documents ON video.document = documents.id
+WHERE documents.uuid = ?"#,
+                    partial_document.uuid
+                )
+                .fetch_one(&self.pool)
+                .await?;
+
+                Content::Video {
+                    text: c.plain,
+                    cues: serde_json::from_str(&c.cues).unwrap_or_default(),
+                }
+            }
+        };
+
+        Ok(Document {
+            title: partial_document.title,
+            url: partial_document.url.parse().unwrap(),
+            uuid: partial_document.uuid,
+            time: partial_document.time,
+            content,
+            metadata: serde_json::from_str(&partial_document.metadata).unwrap(),
+        })
     }
 
     /// List all indexed documents.
@@ -181,8 +342,27 @@ WHERE documents.uuid = ?"#,
 
 #[derive(sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
-enum ContentType {
+pub(crate) enum ContentType {
     WebPage,
+    Pdf,
+    Book,
+    Image,
+    Text,
+    Video,
+}
+
+impl ContentType {
+    /// Type of the given content, as stored in database.
+    pub(crate) fn of(content: &Content) -> ContentType {
+        match content {
+            Content::WebPage { .. } => ContentType::WebPage,
+            Content::Pdf { .. } => ContentType::Pdf,
+            Content::Book { .. } => ContentType::Book,
+            Content::Image { .. } => ContentType::Image,
+            Content::Text { .. } => ContentType::Text,
+            Content::Video { .. } => ContentType::Video,
+        }
+    }
 }
 
 /// Document without fully fetched content. Use `fill_content` to obtain
@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+
+use isahc::http::header::CONTENT_TYPE;
+use isahc::http::{HeaderMap, HeaderValue, Uri};
+use miette::Diagnostic;
+use mime::Mime;
+use thiserror::Error;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum LocalError {
+    #[error("Path {0:?} cannot be turned into URI.")]
+    InvalidPath(PathBuf),
+
+    #[error("{0} is neither an existing file nor a valid URL.")]
+    InvalidTarget(String),
+
+    #[error("{0:?} is a directory, only files can be added.")]
+    Directory(PathBuf),
+
+    #[error("Could not read file.")]
+    Io(#[from] std::io::Error),
+}
+
+/// Interpret `target` given by user either as a path of local file, as a `file:` URI
+/// or as any other URL.
+pub fn target_uri(target: &str) -> Result<Uri, LocalError> {
+    if target.starts_with("file:") {
+        let path = url::Url::parse(target)
+            .ok()
+            .and_then(|u| u.to_file_path().ok())
+            .ok_or_else(|| LocalError::InvalidTarget(target.to_string()))?;
+        if path.is_dir() {
+            return Err(LocalError::Directory(path));
+        }
+        return path_to_uri(&path);
+    }
+
+    let path = Path::new(target);
+
+    if path.is_dir() {
+        Err(LocalError::Directory(path.to_path_buf()))
+    } else if path.is_file() {
+        path_to_uri(&path.canonicalize()?)
+    } else {
+        target
+            .parse()
+            .map_err(|_| LocalError::InvalidTarget(target.to_string()))
+    }
+}
+
+/// Turn absolute `path` into `file:` URI. Since [`Uri`] does not allow empty
+/// authority, such URIs always use `localhost` as host, e. g.
+/// `file://localhost/home/me/page.html`.
+pub fn path_to_uri(path: &Path) -> Result<Uri, LocalError> {
+    let url =
+        url::Url::from_file_path(path).map_err(|_| LocalError::InvalidPath(path.to_path_buf()))?;
+
+    format!("file://localhost{}", url.path())
+        .parse()
+        .map_err(|_| LocalError::InvalidPath(path.to_path_buf()))
+}
+
+/// Turn `file:` URI back into path. Returns `None` for any other URI.
+pub fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    if uri.scheme_str() != Some("file") {
+        return None;
+    }
+
+    url::Url::parse(&uri.to_string()).ok()?.to_file_path().ok()
+}
+
+/// Read local file identified by `file:` URI. Content type is detected
+/// from extension of the file, or from its content if the extension is unknown,
+/// and returned as if it were an HTTP header. If `needs_body` decides, based on
+/// the extension, that the content is not needed, it is not read at all.
+pub async fn read_file(
+    uri: &Uri,
+    needs_body: impl FnOnce(Option<&Mime>) -> bool,
+) -> Result<(HeaderMap, Vec<u8>), LocalError> {
+    let path = uri_to_path(uri).ok_or_else(|| LocalError::InvalidTarget(uri.to_string()))?;
+
+    let by_extension = mime_from_extension(&path);
+
+    let body = if needs_body(by_extension.as_ref()) {
+        tokio::fs::read(&path).await?
+    } else {
+        vec![]
+    };
+
+    let mime = by_extension
+        .or_else(|| sniff(&body))
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(mime.as_ref()).expect("Mime is always a valid header."),
+    );
+
+    Ok((headers, body))
+}
+
+/// Guess content type from extension of file.
+fn mime_from_extension(path: &Path) -> Option<Mime> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+
+    let mime = match extension.as_str() {
+        "html" | "htm" | "xhtml" => "text/html",
+        "txt" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "eml" => "message/rfc822",
+        "pdf" => "application/pdf",
+        "epub" => "application/epub+zip",
+        "ipynb" => "application/x-ipynb+json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "tif" | "tiff" => "image/tiff",
+        "heic" => "image/heic",
+        "vtt" => "text/vtt",
+        "srt" => "application/x-subrip",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mkv" => "video/x-matroska",
+        "mov" => "video/quicktime",
+        _ => return None,
+    };
+
+    mime.parse().ok()
+}
+
+/// Guess content type from the first bytes of content.
+fn sniff(body: &[u8]) -> Option<Mime> {
+    let start = &body[..body.len().min(1024)];
+    let text = String::from_utf8_lossy(start);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+
+    let mime = if start.starts_with(b"%PDF-") {
+        mime::APPLICATION_PDF
+    } else if start.starts_with(b"PK\x03\x04")
+        && start.get(30..58) == Some(b"mimetypeapplication/epub+zip")
+    {
+        "application/epub+zip".parse().ok()?
+    } else if start.starts_with(b"\x89PNG\r\n\x1a\n") {
+        mime::IMAGE_PNG
+    } else if start.starts_with(&[0xff, 0xd8, 0xff]) {
+        mime::IMAGE_JPEG
+    } else if start.starts_with(b"GIF87a") || start.starts_with(b"GIF89a") {
+        mime::IMAGE_GIF
+    } else if start.starts_with(b"RIFF") && start.get(8..12) == Some(b"WEBP") {
+        "image/webp".parse().ok()?
+    } else if text.starts_with("WEBVTT") {
+        "text/vtt".parse().ok()?
+    } else if text.to_lowercase().starts_with("<!doctype html")
+        || text.to_lowercase().contains("<html")
+    {
+        mime::TEXT_HTML
+    } else {
+        return None;
+    };
+
+    Some(mime)
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{path_to_uri, sniff, target_uri, uri_to_path, LocalError};
+
+    #[test]
+    fn file_uri_roundtrip() {
+        let path = Path::new("/tmp/saved pages/článek.html");
+        let uri = path_to_uri(path).unwrap();
+
+        assert_eq!(
+            uri.to_string(),
+            "file://localhost/tmp/saved%20pages/%C4%8Dl%C3%A1nek.html"
+        );
+        assert_eq!(uri_to_path(&uri).unwrap(), path);
+    }
+
+    #[test]
+    fn directory_is_not_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("page.html");
+        std::fs::write(&file, "<html></html>").unwrap();
+
+        assert!(matches!(
+            target_uri(dir.path().to_str().unwrap()),
+            Err(LocalError::Directory(_))
+        ));
+        assert!(matches!(
+            target_uri(&path_to_uri(dir.path()).unwrap().to_string()),
+            Err(LocalError::Directory(_))
+        ));
+        assert_eq!(
+            target_uri(file.to_str().unwrap()).unwrap(),
+            path_to_uri(&file.canonicalize().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn sniff_content() {
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some(mime::APPLICATION_PDF));
+        assert_eq!(
+            sniff(b"\n  <!DOCTYPE html><html></html>"),
+            Some(mime::TEXT_HTML)
+        );
+        assert_eq!(sniff(b"just some text"), None);
+    }
+}
@@ -16,10 +16,33 @@ pub struct SeenOptions {
     pub always_in_foreground: bool,
     /// Use Tor when downloading from the internet.
     pub use_tor: bool,
+    /// Address of SOCKS proxy of Tor (`socks5h://127.0.0.1:9050` if missing).
+    pub tor_proxy: Option<String>,
+    /// Refuse to download from the internet other than through Tor, even if
+    /// preferences of a URL ask for different route.
+    #[serde(default)]
+    pub require_tor: bool,
+    /// Proxy for downloading from the internet (e. g. `http://proxy:3128`
+    /// or `socks5h://127.0.0.1:1080`), unless Tor is used.
+    pub proxy: Option<String>,
+    /// Maximum number of redirects followed when downloading (10 if missing).
+    pub max_redirects: Option<usize>,
+    /// How many requests to one host may run at once (2 if missing).
+    pub host_concurrency: Option<usize>,
+    /// Seconds between starts of requests to one host (1 if missing). Longer
+    /// delay asked for in robots.txt takes precedence.
+    pub host_delay: Option<f64>,
     /// Include timestamp in indexed documents.
     pub include_time: bool,
     /// Default extract.
     pub extract: Extraction,
+    /// Command for recognizing text in images, e. g. `["tesseract", "{file}", "-"]`,
+    /// where `{file}` is replaced by path to the image. Text in images is not
+    /// recognized if missing.
+    pub ocr_command: Option<Vec<String>>,
+    /// Index outputs of code cells of Jupyter notebooks, not only the code.
+    #[serde(default)]
+    pub notebook_outputs: bool,
 }
 
 pub fn extract<'a>(options: &'a SeenOptions, preferences: &'a Preferences) -> &'a Extraction {
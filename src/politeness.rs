@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use isahc::http::{HeaderValue, Uri};
+use isahc::HttpClient;
+use tokio::sync::{Mutex as AsyncMutex, OnceCell, OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::job::MAX_RETRY_WAIT;
+use crate::options::SeenOptions;
+use crate::robots::Robots;
+
+/// How many requests to one host may run at once, unless configured otherwise.
+const DEFAULT_HOST_CONCURRENCY: usize = 2;
+
+/// Seconds between starts of requests to one host, unless configured otherwise.
+const DEFAULT_HOST_DELAY: f64 = 1.0;
+
+/// Schedules requests so that no host gets more of them than it asks for
+/// (in robots.txt or by `Retry-After`) or than configured.
+#[derive(Debug)]
+pub(crate) struct Politeness {
+    hosts: Mutex<HashMap<String, Arc<Host>>>,
+    concurrency: usize,
+    delay: Duration,
+}
+
+#[derive(Debug)]
+struct Host {
+    permits: Arc<Semaphore>,
+    /// When the next request may start.
+    next: AsyncMutex<Instant>,
+    robots: OnceCell<Robots>,
+}
+
+impl Politeness {
+    pub(crate) fn new(options: &SeenOptions) -> Politeness {
+        Politeness {
+            hosts: Mutex::new(HashMap::new()),
+            concurrency: options
+                .host_concurrency
+                .unwrap_or(DEFAULT_HOST_CONCURRENCY)
+                .max(1),
+            delay: options
+                .host_delay
+                .and_then(delay)
+                .unwrap_or_else(|| Duration::from_secs_f64(DEFAULT_HOST_DELAY)),
+        }
+    }
+
+    fn host(&self, url: &Uri) -> Arc<Host> {
+        let key = url
+            .authority()
+            .map(|a| a.as_str().to_lowercase())
+            .unwrap_or_default();
+
+        self.hosts
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| {
+                Arc::new(Host {
+                    permits: Arc::new(Semaphore::new(self.concurrency)),
+                    next: AsyncMutex::new(Instant::now()),
+                    robots: OnceCell::new(),
+                })
+            })
+            .clone()
+    }
+
+    /// Wait until a request to host of `url` may start. The request has to
+    /// be finished before the returned permit is dropped.
+    pub(crate) async fn acquire(&self, url: &Uri) -> OwnedSemaphorePermit {
+        let host = self.host(url);
+
+        let permit = host
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Semaphore is never closed.");
+
+        let crawl_delay = host
+            .robots
+            .get()
+            .and_then(|r| r.crawl_delay)
+            .and_then(delay)
+            .unwrap_or_default();
+
+        let mut next = host.next.lock().await;
+        sleep_until(*next).await;
+        *next = Instant::now() + self.delay.max(crawl_delay);
+
+        permit
+    }
+
+    /// Postpone requests to host of `url` by `wait`, e. g. when it asked us
+    /// to slow down.
+    pub(crate) async fn back_off(&self, url: &Uri, wait: Duration) {
+        let host = self.host(url);
+        let mut next = host.next.lock().await;
+        *next = (*next).max(Instant::now() + wait);
+    }
+
+    /// Rules of robots.txt of host of `url`, downloaded once per host.
+    pub(crate) async fn robots(&self, http_client: &HttpClient, url: &Uri) -> Robots {
+        self.host(url)
+            .robots
+            .get_or_init(|| Robots::fetch(http_client, url))
+            .await
+            .clone()
+    }
+}
+
+/// Delay of `seconds` between requests, at most [`MAX_RETRY_WAIT`] so that
+/// a host cannot stall us. None unless `seconds` is finite.
+fn delay(seconds: f64) -> Option<Duration> {
+    seconds
+        .is_finite()
+        .then(|| Duration::from_secs_f64(seconds.clamp(0.0, MAX_RETRY_WAIT.as_secs_f64())))
+}
+
+/// How long to wait according to `Retry-After` header, given either
+/// in seconds or as a date.
+pub(crate) fn retry_after(value: &HeaderValue, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use isahc::http::HeaderValue;
+    use tokio::time::Duration;
+
+    use super::{delay, retry_after};
+    use crate::job::MAX_RETRY_WAIT;
+
+    #[test]
+    fn delays() {
+        assert_eq!(delay(1.5), Some(Duration::from_millis(1500)));
+        assert_eq!(delay(-1.0), Some(Duration::ZERO));
+        assert_eq!(delay(1e12), Some(MAX_RETRY_WAIT));
+        assert_eq!(delay(f64::INFINITY), None);
+        assert_eq!(delay(f64::NAN), None);
+    }
+
+    #[test]
+    fn retry_after_values() {
+        let now = Utc.with_ymd_and_hms(2023, 3, 14, 10, 0, 0).unwrap();
+        let wait = |v: &'static str| retry_after(&HeaderValue::from_static(v), now);
+
+        assert_eq!(wait("120"), Some(Duration::from_secs(120)));
+        assert_eq!(
+            wait("Tue, 14 Mar 2023 10:01:30 GMT"),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(wait("Tue, 14 Mar 2023 09:00:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(wait("soon"), None);
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Local};
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use miette::Diagnostic;
+use sqlx::SqlitePool;
+use thiserror::Error;
+use tokio::time::{interval, sleep, Duration};
+use uuid::Uuid;
+
+use crate::batch::describe;
+use crate::job::{self, Job};
+use crate::Seen;
+
+/// How many times a job is tried before it is given up.
+const MAX_ATTEMPTS: i64 = 5;
+
+/// How long the worker waits before looking into an empty queue again.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often a worker confirms that it is still carrying out a job.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Seconds without heartbeat after which a running job is considered
+/// abandoned by its worker and is queued again.
+const STALE_AFTER: i64 = 120;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum QueueError {
+    #[error("Database error.")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+/// State of a queued job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+pub enum JobState {
+    /// Waiting for a worker, possibly to be tried again.
+    Queued,
+    /// Being carried out by a worker.
+    Running,
+    /// Source was added.
+    Done,
+    /// All attempts failed.
+    Failed,
+}
+
+impl Display for JobState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+        };
+        f.write_str(s)
+    }
+}
+
+/// Job in the queue, as listed to user.
+#[derive(Debug)]
+pub struct QueuedJob {
+    pub id: i64,
+    pub url: String,
+    pub state: JobState,
+    pub attempts: i64,
+    pub created: DateTime<Local>,
+    pub updated: DateTime<Local>,
+    /// Error of the last attempt.
+    pub outcome: Option<String>,
+    /// Document created by the job, once it is done.
+    pub document: Option<Uuid>,
+}
+
+/// Put `job` into the queue. Returns ID of the queued job.
+pub async fn enqueue(seen: &Seen, job: &Job) -> Result<i64, QueueError> {
+    let url = job.url.to_string();
+    let js = serde_json::to_string(job).unwrap();
+    let now = Local::now();
+
+    let id = sqlx::query!(
+        "INSERT INTO jobs (url, job, created, updated) VALUES (?, ?, ?, ?)",
+        url,
+        js,
+        now,
+        now
+    )
+    .execute(&seen.pool)
+    .await?
+    .last_insert_rowid();
+
+    Ok(id)
+}
+
+/// All jobs in the queue, including finished ones.
+pub async fn list(seen: &Seen) -> Result<Vec<QueuedJob>, QueueError> {
+    let jobs = sqlx::query_as!(
+        QueuedJob,
+        r#"
+SELECT id, url, state AS "state: JobState", attempts,
+       created AS "created: DateTime<Local>", updated AS "updated: DateTime<Local>",
+       outcome, document AS "document: Uuid"
+FROM jobs
+ORDER BY id"#
+    )
+    .fetch_all(&seen.pool)
+    .await?;
+
+    Ok(jobs)
+}
+
+/// Carry out queued jobs one by one. Failed jobs are tried again later, with
+/// growing delay, unless their failure is permanent. If `exit_when_empty`,
+/// return once there is nothing left to do, otherwise wait for new jobs forever.
+pub async fn work(seen: &Seen, exit_when_empty: bool) -> Result<(), QueueError> {
+    loop {
+        let now = Local::now().timestamp();
+
+        recover(&seen.pool, now).await?;
+
+        let next = match claim(&seen.pool, now).await? {
+            Some(next) => next,
+            None if exit_when_empty => return Ok(()),
+            None => {
+                sleep(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        let attempts = next.attempts + 1;
+
+        let (state, not_before, outcome, document) = match serde_json::from_str::<Job>(&next.job) {
+            Ok(job) => {
+                println!("Job {}: {}", next.id, job.url);
+                tokio::select! {
+                    outcome = run(seen, job, attempts, now) => outcome,
+                    _ = heartbeat(&seen.pool, next.id) => unreachable!("Heartbeat never stops."),
+                }
+            }
+            Err(e) => (
+                JobState::Failed,
+                0,
+                Some(format!("Job cannot be read: {e}")),
+                None,
+            ),
+        };
+
+        match state {
+            JobState::Done => println!("Job {}: done.", next.id),
+            JobState::Queued => println!("Job {}: will try again later.", next.id),
+            _ => println!("Job {}: failed.", next.id),
+        }
+
+        let updated = Local::now();
+
+        sqlx::query!(
+            "UPDATE jobs SET state = ?, attempts = ?, not_before = ?, outcome = ?, document = ?, updated = ? WHERE id = ?",
+            state,
+            attempts,
+            not_before,
+            outcome,
+            document,
+            updated,
+            next.id
+        )
+        .execute(&seen.pool)
+        .await?;
+    }
+}
+
+/// Job taken from the queue by a worker.
+#[derive(Debug)]
+struct Claimed {
+    id: i64,
+    job: String,
+    attempts: i64,
+}
+
+/// Take the next job that may be carried out at `now` (in seconds) and mark
+/// it as running. It is done in one statement, so that no two workers can
+/// take the same job.
+async fn claim(pool: &SqlitePool, now: i64) -> Result<Option<Claimed>, sqlx::Error> {
+    sqlx::query_as!(
+        Claimed,
+        r#"
+UPDATE jobs SET state = 'running', heartbeat = ?
+WHERE id = (SELECT id FROM jobs WHERE state = 'queued' AND not_before <= ? ORDER BY id LIMIT 1)
+  AND state = 'queued'
+RETURNING id AS "id!", job AS "job!", attempts AS "attempts!""#,
+        now,
+        now
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Queue again jobs left running by a worker that stopped without finishing
+/// them, i. e. those without heartbeat for a while.
+async fn recover(pool: &SqlitePool, now: i64) -> Result<(), sqlx::Error> {
+    let stale = now - STALE_AFTER;
+
+    sqlx::query!(
+        "UPDATE jobs SET state = 'queued' WHERE state = 'running' AND heartbeat < ?",
+        stale
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Regularly confirms that job `id` is still being carried out. It is expected
+/// to be cancelled externally once the job has finished, e. g. by [`tokio::select!`].
+async fn heartbeat(pool: &SqlitePool, id: i64) {
+    let mut int = interval(HEARTBEAT_INTERVAL);
+
+    loop {
+        int.tick().await;
+        let now = Local::now().timestamp();
+        // Failed heartbeat only risks that the job is carried out twice.
+        let _ = sqlx::query!("UPDATE jobs SET heartbeat = ? WHERE id = ?", now, id)
+            .execute(pool)
+            .await;
+    }
+}
+
+/// Carry out `job` for the `attempts`-th time. Returns new state of the job,
+/// time (in seconds) before which it should not be tried again, error message
+/// and document that was created.
+async fn run(
+    seen: &Seen,
+    job: Job,
+    attempts: i64,
+    now: i64,
+) -> (JobState, i64, Option<String>, Option<Uuid>) {
+    let multi = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+
+    match job::add(seen, job, false, &multi).await {
+        Ok(uuid) => (JobState::Done, 0, None, uuid),
+        Err(e) => {
+            let state = if e.is_transient() && attempts < MAX_ATTEMPTS {
+                JobState::Queued
+            } else {
+                JobState::Failed
+            };
+
+            // One minute after first failure, then 2, 4, 8… minutes, unless
+            // the server asked for longer.
+            let delay = 60 * 2_i64.pow(attempts as u32 - 1);
+            let delay = e
+                .retry_after()
+                .map(|d| delay.max(d.as_secs() as i64))
+                .unwrap_or(delay);
+            (state, now + delay, Some(describe(&e)), None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::sqlite::SqlitePoolOptions;
+    use sqlx::SqlitePool;
+
+    use super::{claim, recover, STALE_AFTER};
+
+    const NOW: i64 = 1_700_000_000;
+
+    async fn pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        pool
+    }
+
+    async fn insert(pool: &SqlitePool, state: &str, not_before: i64, heartbeat: i64) -> i64 {
+        sqlx::query(
+            "INSERT INTO jobs (url, job, state, created, updated, not_before, heartbeat) VALUES ('https://example.com/', '{}', ?, '', '', ?, ?)",
+        )
+        .bind(state)
+        .bind(not_before)
+        .bind(heartbeat)
+        .execute(pool)
+        .await
+        .unwrap()
+        .last_insert_rowid()
+    }
+
+    async fn state(pool: &SqlitePool, id: i64) -> String {
+        sqlx::query_scalar("SELECT state FROM jobs WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn claim_each_job_once() {
+        let pool = pool().await;
+        let first = insert(&pool, "queued", 0, 0).await;
+        let second = insert(&pool, "queued", 0, 0).await;
+        let later = insert(&pool, "queued", NOW + 60, 0).await;
+
+        let (a, b) = tokio::join!(claim(&pool, NOW), claim(&pool, NOW));
+        let mut claimed = vec![a.unwrap().unwrap().id, b.unwrap().unwrap().id];
+        claimed.sort();
+
+        assert_eq!(claimed, vec![first, second]);
+        assert_eq!(state(&pool, first).await, "running");
+        assert!(claim(&pool, NOW).await.unwrap().is_none());
+        assert_eq!(claim(&pool, NOW + 60).await.unwrap().unwrap().id, later);
+    }
+
+    #[tokio::test]
+    async fn recover_only_stale_jobs() {
+        let pool = pool().await;
+        let alive = insert(&pool, "running", 0, NOW - 10).await;
+        let stale = insert(&pool, "running", 0, NOW - STALE_AFTER - 1).await;
+        let done = insert(&pool, "done", 0, 0).await;
+
+        recover(&pool, NOW).await.unwrap();
+
+        assert_eq!(state(&pool, alive).await, "running");
+        assert_eq!(state(&pool, stale).await, "queued");
+        assert_eq!(state(&pool, done).await, "done");
+    }
+}
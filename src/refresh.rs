@@ -0,0 +1,268 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Duration, Local};
+use indicatif::{ProgressBar, ProgressStyle};
+use isahc::http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use isahc::http::HeaderMap;
+use miette::Diagnostic;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::archive::{archive_source, latest_headers};
+use crate::batch::{describe, Report, Status};
+use crate::document::{Content, Document, Prepare};
+use crate::job::{download_source, index_source, JobError, SourceHints};
+use crate::url_preferences::{self, Preferences, UrlPreferences};
+use crate::{local, Seen, SeenError};
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum RefreshError {
+    #[error("Could not load documents.")]
+    Seen(#[from] SeenError),
+
+    #[error("Database error.")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Which documents to refresh.
+#[derive(Debug)]
+pub enum Selection {
+    Document(Uuid),
+    All,
+    /// Documents seen longer ago than this.
+    OlderThan(Duration),
+}
+
+/// Download documents of `selection` again and re-index those whose text
+/// has changed. Requests are conditional on validators (ETag, Last-Modified)
+/// of the archived source, if there is one, so that unchanged content is
+/// not even downloaded.
+pub async fn refresh(
+    seen: &Seen,
+    selection: &Selection,
+    archive: bool,
+    dry_run: bool,
+) -> Result<Vec<Report>, RefreshError> {
+    let documents = match selection {
+        Selection::Document(uuid) => vec![seen.get(uuid).await?],
+        Selection::All => seen.list().await?,
+        Selection::OlderThan(age) => {
+            let before = Local::now() - *age;
+            let mut documents = seen.list().await?;
+            documents.retain(|d| d.time < before);
+            documents
+        }
+    };
+
+    // Archive is keyed by the effective URL.
+    let mut effective_urls = vec![];
+    for document in &documents {
+        let url = effective_url(seen, &document.uuid)
+            .await?
+            .unwrap_or_else(|| document.url.to_string());
+        effective_urls.push(url);
+    }
+
+    let headers = latest_headers(
+        seen,
+        &effective_urls.iter().cloned().collect::<HashSet<_>>(),
+    )
+    .await;
+
+    let pb = ProgressBar::new(documents.len() as u64);
+    pb.set_style(
+        ProgressStyle::with_template("{bar:40.green/yellow} {pos:>7}/{len:7} {wide_msg}").unwrap(),
+    );
+
+    let mut reports = vec![];
+
+    for (document, effective_url) in documents.into_iter().zip(effective_urls) {
+        let target = document.url.to_string();
+        pb.set_message(target.clone());
+
+        let conditional = headers
+            .get(&effective_url)
+            .map(validators)
+            .unwrap_or_default();
+
+        let status = refresh_document(seen, document, conditional, archive, dry_run).await;
+
+        pb.inc(1);
+        reports.push(Report { target, status });
+    }
+
+    pb.finish_and_clear();
+
+    Ok(reports)
+}
+
+async fn refresh_document(
+    seen: &Seen,
+    document: Document,
+    conditional: HeaderMap,
+    archive: bool,
+    dry_run: bool,
+) -> Status {
+    let url = document.url.clone();
+
+    if !matches!(url.scheme_str(), Some("http" | "https" | "file")) {
+        return Status::Skipped("cannot be downloaded again".to_string());
+    }
+
+    if let Content::Video { .. } = document.content {
+        return Status::Skipped("videos are not refreshed".to_string());
+    }
+
+    let preferences = match url_preferences::for_url(&url, seen).await {
+        Some(UrlPreferences::Blacklist) => return Status::Skipped("blacklisted".to_string()),
+        Some(UrlPreferences::Preferences(p)) => p,
+        None => Preferences::default(),
+    };
+
+    let hints = SourceHints {
+        conditional,
+        ..Default::default()
+    };
+
+    let (source, redirects) =
+        match download_source(seen, &url, &preferences, &hints, ProgressBar::hidden()).await {
+            Ok(downloaded) => downloaded,
+            Err(JobError::NotModified) => return Status::Skipped("not modified".to_string()),
+            Err(e) => return Status::Failed(describe(&e)),
+        };
+
+    let tags = document
+        .metadata
+        .get("tags")
+        .and_then(|t| serde_json::from_value::<Vec<String>>(t.clone()).ok())
+        .unwrap_or_default();
+
+    let mut metadata = HashMap::from([("tag".to_string(), serde_json::to_value(&tags).unwrap())]);
+
+    if let Some(path) = local::uri_to_path(&url) {
+        metadata.insert(
+            "path".to_string(),
+            serde_json::to_value(path.to_string_lossy()).unwrap(),
+        );
+    }
+
+    // Keep the time when the document was originally seen.
+    let time = document.time;
+
+    let refreshed = source.prepare_document(metadata.clone(), &seen.options, &preferences, time);
+
+    if refreshed.content.plain_text() == document.content.plain_text() {
+        return Status::Skipped("text unchanged".to_string());
+    }
+
+    if dry_run {
+        return Status::Updated(None);
+    }
+
+    if archive {
+        archive_source(seen, &source, &metadata, time).await;
+    }
+
+    match index_source(
+        seen,
+        &url,
+        &redirects,
+        source,
+        &preferences,
+        metadata,
+        time,
+        &tags,
+    )
+    .await
+    {
+        Ok(uuid) => Status::Updated(Some(uuid)),
+        Err(e) => Status::Failed(describe(&e)),
+    }
+}
+
+/// URL to which document `uuid` was redirected last, if it was.
+async fn effective_url(seen: &Seen, uuid: &Uuid) -> Result<Option<String>, sqlx::Error> {
+    let url = sqlx::query!(
+        "SELECT redirects.url FROM redirects JOIN documents ON redirects.document = documents.id WHERE documents.uuid = ? ORDER BY redirects.position DESC LIMIT 1",
+        uuid
+    )
+    .fetch_optional(&seen.pool)
+    .await?
+    .and_then(|r| r.url);
+
+    Ok(url)
+}
+
+/// Headers of a conditional request for content that was returned with `headers`.
+fn validators(headers: &HeaderMap) -> HeaderMap {
+    let mut conditional = HeaderMap::new();
+
+    if let Some(etag) = headers.get(ETAG) {
+        conditional.insert(IF_NONE_MATCH, etag.clone());
+    }
+
+    if let Some(last_modified) = headers.get(LAST_MODIFIED) {
+        conditional.insert(IF_MODIFIED_SINCE, last_modified.clone());
+    }
+
+    conditional
+}
+
+/// Parse age such as `30d`, a number followed by unit: `m` (minutes),
+/// `h` (hours), `d` (days) or `w` (weeks).
+pub fn parse_age(age: &str) -> Result<Duration, String> {
+    let age = age.trim();
+    let split = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in {age:?}"))?;
+    let (number, unit) = age.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid number in {age:?}"))?;
+
+    match unit {
+        "m" => Ok(Duration::minutes(number)),
+        "h" => Ok(Duration::hours(number)),
+        "d" => Ok(Duration::days(number)),
+        "w" => Ok(Duration::weeks(number)),
+        _ => Err(format!("unknown unit {unit:?}, use m, h, d or w")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use isahc::http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+    use isahc::http::{HeaderMap, HeaderValue};
+
+    use super::{parse_age, validators};
+
+    #[test]
+    fn ages() {
+        assert_eq!(parse_age("30d"), Ok(Duration::days(30)));
+        assert_eq!(parse_age("12h"), Ok(Duration::hours(12)));
+        assert_eq!(parse_age("2w"), Ok(Duration::weeks(2)));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3y").is_err());
+    }
+
+    #[test]
+    fn conditional_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Tue, 14 Mar 2023 10:00:00 GMT"),
+        );
+
+        let conditional = validators(&headers);
+
+        assert_eq!(conditional.get(IF_NONE_MATCH).unwrap(), "\"abc\"");
+        assert_eq!(
+            conditional.get(IF_MODIFIED_SINCE).unwrap(),
+            "Tue, 14 Mar 2023 10:00:00 GMT"
+        );
+        assert_eq!(validators(&HeaderMap::new()).len(), 0);
+    }
+}
@@ -0,0 +1,198 @@
+use chromiumoxide::cdp::browser_protocol::fetch::{
+    self, ContinueRequestParams, EventRequestPaused, HeaderEntry,
+};
+use chromiumoxide::cdp::browser_protocol::network::{self, EventResponseReceived, ResourceType};
+use chromiumoxide::cdp::browser_protocol::page::{
+    EventLifecycleEvent, SetLifecycleEventsEnabledParams,
+};
+use chromiumoxide::error::CdpError;
+use chromiumoxide::{Browser, BrowserConfig};
+use futures::{FutureExt, StreamExt};
+use isahc::http::header::COOKIE;
+use isahc::http::{StatusCode, Uri};
+use miette::Diagnostic;
+use thiserror::Error;
+use tokio::time::{timeout, Duration};
+
+use crate::request::{self, RequestError};
+use crate::url_preferences::RequestSettings;
+
+/// How long to wait for the page to stop loading resources.
+const NETWORK_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum RenderError {
+    #[error("Could not start headless Chromium: {0}")]
+    Launch(String),
+
+    #[error("Headless Chromium failed.")]
+    Browser(#[from] CdpError),
+
+    #[error("Could not prepare request.")]
+    Request(#[from] RequestError),
+}
+
+/// Page loaded in headless Chromium.
+#[derive(Debug)]
+pub struct Rendered {
+    /// Effective URL (after redirects).
+    pub url: Uri,
+    /// Status of response to the main frame, unless there was none.
+    pub status: Option<StatusCode>,
+    /// Serialized DOM of the page.
+    pub html: String,
+}
+
+/// Load page at `url` in headless Chromium, wait until it stops loading
+/// resources (or until [`NETWORK_IDLE_TIMEOUT`]) and serialize its DOM.
+/// Browser connects through `proxy`, if given. Requests of the page are
+/// customised by `settings` like those we make ourselves (see
+/// [`request::build`]).
+pub async fn render(
+    url: &Uri,
+    settings: &RequestSettings,
+    proxy: Option<&Uri>,
+) -> Result<Rendered, RenderError> {
+    // Fail early if the settings are unusable, e. g. a secret is missing.
+    request::build(url, url.host(), settings, proxy).await?;
+
+    let mut config = BrowserConfig::builder();
+
+    if let Some(proxy) = proxy {
+        // Chromium resolves names through SOCKS5 proxy on its own and does
+        // not know socks5h.
+        let proxy = proxy.to_string().replacen("socks5h://", "socks5://", 1);
+        config = config.arg(format!("--proxy-server={}", proxy.trim_end_matches('/')));
+    }
+
+    let config = config.build().map_err(RenderError::Launch)?;
+
+    let (browser, mut handler) = Browser::launch(config).await?;
+
+    let handle = tokio::task::spawn(async move {
+        while let Some(event) = handler.next().await {
+            if event.is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = async {
+        let page = browser.new_page("about:blank").await?;
+
+        if let Some(user_agent) = &settings.user_agent {
+            page.set_user_agent(user_agent).await?;
+        }
+
+        // Requests are paused until given headers we would send.
+        let mut paused = page.event_listener::<EventRequestPaused>().await?;
+        page.execute(fetch::EnableParams::default()).await?;
+
+        let interceptor = page.clone();
+        let host = url.host().map(|h| h.to_string());
+        let settings = settings.clone();
+        let intercept = tokio::task::spawn(async move {
+            while let Some(event) = paused.next().await {
+                let mut params = ContinueRequestParams::new(event.request_id.clone());
+                params.headers = request_headers(&event.request, host.as_deref(), &settings).await;
+                if interceptor.execute(params).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        page.execute(SetLifecycleEventsEnabledParams::new(true))
+            .await?;
+        let mut lifecycle = page.event_listener::<EventLifecycleEvent>().await?;
+        let mut responses = page.event_listener::<EventResponseReceived>().await?;
+
+        page.goto(url.to_string()).await?;
+
+        // Pages which keep polling never get idle, what has loaded so far
+        // has to do.
+        let _ = timeout(NETWORK_IDLE_TIMEOUT, async {
+            while let Some(event) = lifecycle.next().await {
+                if event.name == "networkIdle" {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        intercept.abort();
+
+        let html = page.content().await?;
+
+        let effective_url = page
+            .url()
+            .await?
+            .and_then(|u| u.parse().ok())
+            .unwrap_or_else(|| url.clone());
+
+        // The first document loaded into the main frame is the page itself,
+        // later ones come from navigation by scripts.
+        let mainframe = page.mainframe().await?;
+        let mut status = None;
+        while let Some(Some(event)) = responses.next().now_or_never() {
+            if status.is_none()
+                && event.r#type == ResourceType::Document
+                && event.frame_id == mainframe
+            {
+                status = u16::try_from(event.response.status)
+                    .ok()
+                    .and_then(|s| StatusCode::from_u16(s).ok());
+            }
+        }
+
+        Ok(Rendered {
+            url: effective_url,
+            status,
+            html,
+        })
+    }
+    .await;
+
+    // Dropping the browser kills its process.
+    drop(browser);
+    handle.abort();
+
+    result
+}
+
+/// Headers of `request` made by a page, completed with those we would send
+/// ourselves (see [`request::build`]) for `host` of the rendered page. None
+/// if the request is to be left alone.
+async fn request_headers(
+    request: &network::Request,
+    host: Option<&str>,
+    settings: &RequestSettings,
+) -> Option<Vec<HeaderEntry>> {
+    let url = request.url.parse().ok()?;
+    let ours = request::build(&url, host, settings, None).await.ok()?;
+    let theirs = request.headers.inner().as_object()?;
+
+    let mut headers = theirs
+        .iter()
+        .filter(|(name, _)| !ours.headers().contains_key(name.as_str()))
+        .filter_map(|(name, value)| Some(HeaderEntry::new(name, value.as_str()?)))
+        .collect::<Vec<_>>();
+
+    for (name, value) in ours.headers() {
+        let mut value = value.to_str().ok()?.to_string();
+
+        // Cookies set by the page are kept along with ours.
+        if name == COOKIE {
+            if let Some(cookies) = theirs
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(COOKIE.as_str()))
+                .and_then(|(_, v)| v.as_str())
+            {
+                value = format!("{cookies}; {value}");
+            }
+        }
+
+        headers.push(HeaderEntry::new(name.as_str(), value));
+    }
+
+    Some(headers)
+}
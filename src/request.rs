@@ -0,0 +1,239 @@
+use chrono::Utc;
+use isahc::config::{Configurable, RedirectPolicy};
+use isahc::http::header::{AUTHORIZATION, COOKIE, USER_AGENT};
+use isahc::http::Uri;
+use isahc::Request;
+use miette::Diagnostic;
+use thiserror::Error;
+use tokio::time::Duration;
+
+use crate::options::SeenOptions;
+use crate::url_preferences::{Auth, RequestSettings, Route, Secret};
+
+/// SOCKS proxy of Tor, unless configured otherwise.
+const DEFAULT_TOR_PROXY: &str = "socks5h://127.0.0.1:9050";
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum RequestError {
+    #[error("Environment variable {0} with secret is not set.")]
+    MissingEnv(String),
+
+    #[error("Could not read secret from keyring.")]
+    Keyring(#[from] keyring::Error),
+
+    #[error("Could not read cookie file.")]
+    CookieFile(#[from] std::io::Error),
+
+    #[error("Invalid request: {0}")]
+    Invalid(String),
+
+    #[error("Tor is required, refusing to use {0}.")]
+    TorRequired(Route),
+
+    #[error("Invalid proxy {0:?}.")]
+    InvalidProxy(String),
+}
+
+/// Route used unless preferences of a URL ask for another one.
+pub(crate) fn default_route(options: &SeenOptions) -> Route {
+    if options.use_tor || options.require_tor {
+        Route::Tor
+    } else if let Some(proxy) = &options.proxy {
+        Route::Proxy(proxy.clone())
+    } else {
+        Route::Direct
+    }
+}
+
+/// Route of requests customised by `settings`.
+pub(crate) fn route(
+    options: &SeenOptions,
+    settings: &RequestSettings,
+) -> Result<Route, RequestError> {
+    let route = settings
+        .route
+        .clone()
+        .unwrap_or_else(|| default_route(options));
+
+    if options.require_tor && route != Route::Tor {
+        Err(RequestError::TorRequired(route))
+    } else {
+        Ok(route)
+    }
+}
+
+/// URL of proxy of `route`, none for direct connection.
+pub(crate) fn proxy(route: &Route, options: &SeenOptions) -> Result<Option<Uri>, RequestError> {
+    let proxy = match route {
+        Route::Direct => return Ok(None),
+        Route::Tor => options.tor_proxy.as_deref().unwrap_or(DEFAULT_TOR_PROXY),
+        Route::Proxy(proxy) => proxy.as_str(),
+    };
+
+    proxy
+        .parse()
+        .map(Some)
+        .map_err(|_| RequestError::InvalidProxy(proxy.to_string()))
+}
+
+/// GET request for `url` customised by `settings`, made through `proxy` (or
+/// directly if none). Redirects are not followed. Credentials and additional
+/// headers are only sent to `host` (that of the requested URL), so that
+/// redirects cannot leak them elsewhere.
+pub(crate) async fn build(
+    url: &Uri,
+    host: Option<&str>,
+    settings: &RequestSettings,
+    proxy: Option<&Uri>,
+) -> Result<Request<()>, RequestError> {
+    let mut builder = Request::get(url)
+        .redirect_policy(RedirectPolicy::None)
+        .proxy(proxy.cloned());
+
+    if let Some(timeout) = settings.timeout {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+
+    if let Some(user_agent) = &settings.user_agent {
+        builder = builder.header(USER_AGENT, user_agent);
+    }
+
+    if url.host() == host {
+        for (name, value) in &settings.headers {
+            builder = builder.header(name, value);
+        }
+
+        match &settings.auth {
+            Some(Auth::Basic { username, password }) => {
+                let credentials = base64::encode(format!("{username}:{}", reveal(password)?));
+                builder = builder.header(AUTHORIZATION, format!("Basic {credentials}"));
+            }
+            Some(Auth::Bearer { token }) => {
+                builder = builder.header(AUTHORIZATION, format!("Bearer {}", reveal(token)?));
+            }
+            None => (),
+        }
+    }
+
+    if let Some(file) = &settings.cookie_file {
+        let jar = tokio::fs::read_to_string(file).await?;
+        if let Some(cookies) = cookie_header(&jar, url, Utc::now().timestamp()) {
+            builder = builder.header(COOKIE, cookies);
+        }
+    }
+
+    builder
+        .body(())
+        .map_err(|e| RequestError::Invalid(e.to_string()))
+}
+
+fn reveal(secret: &Secret) -> Result<String, RequestError> {
+    match secret {
+        Secret::Env(name) => {
+            std::env::var(name).map_err(|_| RequestError::MissingEnv(name.clone()))
+        }
+        Secret::Keyring { service, user } => {
+            Ok(keyring::Entry::new(service, user)?.get_password()?)
+        }
+    }
+}
+
+/// Value of `Cookie` header with cookies of Netscape cookie file `jar` which
+/// belong to `url` and have not expired at `now` (Unix timestamp).
+fn cookie_header(jar: &str, url: &Uri, now: i64) -> Option<String> {
+    let host = url.host()?.to_lowercase();
+    let path = url.path();
+    let https = url.scheme_str() == Some("https");
+
+    let cookies = jar
+        .lines()
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .filter_map(|line| {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            match fields[..] {
+                [domain, subdomains, cookie_path, secure, expires, name, value] => {
+                    let domain = domain.to_lowercase();
+                    let subdomains = subdomains == "TRUE" || domain.starts_with('.');
+                    let domain = domain.trim_start_matches('.');
+
+                    let domain_matches =
+                        host == domain || (subdomains && host.ends_with(&format!(".{domain}")));
+                    let path_matches = path.starts_with(cookie_path)
+                        && (cookie_path.ends_with('/')
+                            || path.len() == cookie_path.len()
+                            || path[cookie_path.len()..].starts_with('/'));
+                    let expires = expires.parse::<i64>().unwrap_or(0);
+                    let valid = expires == 0 || expires > now;
+
+                    (domain_matches && path_matches && valid && (https || secure != "TRUE"))
+                        .then(|| format!("{name}={value}"))
+                }
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    (!cookies.is_empty()).then(|| cookies.join("; "))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cookie_header, proxy, route};
+    use crate::options::SeenOptions;
+    use crate::url_preferences::{RequestSettings, Route};
+
+    #[test]
+    fn routes() {
+        let settings = |route| RequestSettings {
+            route,
+            ..Default::default()
+        };
+
+        let mut options = SeenOptions {
+            proxy: Some("http://proxy:3128".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            route(&options, &settings(None)).unwrap(),
+            Route::Proxy("http://proxy:3128".to_string())
+        );
+        assert_eq!(
+            route(&options, &settings(Some(Route::Direct))).unwrap(),
+            Route::Direct
+        );
+
+        options.require_tor = true;
+        assert_eq!(route(&options, &settings(None)).unwrap(), Route::Tor);
+        assert!(route(&options, &settings(Some(Route::Direct))).is_err());
+        assert_eq!(
+            proxy(&Route::Tor, &options).unwrap().unwrap().to_string(),
+            "socks5h://127.0.0.1:9050/"
+        );
+    }
+
+    #[test]
+    fn cookies_for_url() {
+        let jar = "# Netscape HTTP Cookie File\n\
+.example.com\tTRUE\t/\tFALSE\t0\tsession\tabc\n\
+#HttpOnly_www.example.com\tFALSE\t/members\tTRUE\t2000000000\tlogin\txyz\n\
+.example.com\tTRUE\t/\tFALSE\t1000\told\tgone\n\
+other.org\tFALSE\t/\tFALSE\t0\tforeign\tno\n";
+
+        let header = |url: &str| cookie_header(jar, &url.parse().unwrap(), 1_700_000_000);
+
+        assert_eq!(
+            header("https://www.example.com/members/page").as_deref(),
+            Some("session=abc; login=xyz")
+        );
+        assert_eq!(
+            header("http://www.example.com/members").as_deref(),
+            Some("session=abc")
+        );
+        assert_eq!(
+            header("https://www.example.com/membership").as_deref(),
+            Some("session=abc")
+        );
+        assert_eq!(header("https://example.net/"), None);
+    }
+}
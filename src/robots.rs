@@ -0,0 +1,213 @@
+use isahc::http::Uri;
+use isahc::prelude::*;
+use isahc::HttpClient;
+
+/// Name under which seen looks for its rules in robots.txt.
+pub const USER_AGENT: &str = "seen";
+
+/// Rules of robots.txt that apply to seen.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Robots {
+    /// Path patterns with information whether they are allowed.
+    rules: Vec<(bool, String)>,
+    /// Seconds to wait between requests.
+    pub crawl_delay: Option<f64>,
+    /// URLs of sitemaps, which apply regardless of user agent.
+    pub sitemaps: Vec<String>,
+}
+
+impl Robots {
+    /// Download robots.txt of the host of `url`. Missing or unreadable robots.txt
+    /// allows everything.
+    pub async fn fetch(http_client: &HttpClient, url: &Uri) -> Robots {
+        let robots_url = match (url.scheme_str(), url.authority()) {
+            (Some(scheme), Some(authority)) => format!("{scheme}://{authority}/robots.txt"),
+            _ => return Robots::default(),
+        };
+
+        match http_client.get_async(robots_url).await {
+            Ok(mut response) if response.status().is_success() => response
+                .text()
+                .await
+                .map(|txt| Robots::parse(&txt, USER_AGENT))
+                .unwrap_or_default(),
+            _ => Robots::default(),
+        }
+    }
+
+    /// Parse robots.txt and keep rules of group for `agent`, or of the group
+    /// for all agents (`*`) if there is no such group. Groups are matched
+    /// by product token (case-insensitive), e. g. `seen` for `seen/1.0`.
+    pub fn parse(txt: &str, agent: &str) -> Robots {
+        let agent = product_token(agent);
+
+        let mut sitemaps = vec![];
+        // Rules of groups for our agent and for all agents.
+        let mut own: Option<Robots> = None;
+        let mut any: Option<Robots> = None;
+
+        // Agents of the current group and whether its rules already started.
+        let mut agents: Vec<String> = vec![];
+        let mut in_rules = false;
+
+        for line in txt.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+
+            match key.as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(product_token(value));
+                }
+                "allow" | "disallow" | "crawl-delay" => {
+                    in_rules = true;
+
+                    let group = if agents.iter().any(|a| !a.is_empty() && *a == agent) {
+                        &mut own
+                    } else if agents.iter().any(|a| a == "*") {
+                        &mut any
+                    } else {
+                        continue;
+                    };
+
+                    let group = group.get_or_insert_with(Robots::default);
+
+                    match key.as_str() {
+                        "crawl-delay" => {
+                            group.crawl_delay = value
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|d| d.is_finite() && *d >= 0.0)
+                        }
+                        // Empty disallow means everything is allowed.
+                        _ if value.is_empty() => (),
+                        _ => group.rules.push((key == "allow", value.to_string())),
+                    }
+                }
+                "sitemap" => sitemaps.push(value.to_string()),
+                _ => (),
+            }
+        }
+
+        let mut robots = own.or(any).unwrap_or_default();
+        robots.sitemaps = sitemaps;
+        robots
+    }
+
+    /// Whether path (with query) of `url` may be visited. The most specific
+    /// (longest) matching rule wins, allowing in case of a tie.
+    pub fn is_allowed(&self, url: &Uri) -> bool {
+        let path = url.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+/// Product token of user agent `agent` (name without version), lower-cased.
+fn product_token(agent: &str) -> String {
+    agent
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+/// Whether `path` matches robots.txt `pattern`, which is a prefix possibly
+/// containing `*` (any characters) and ending with `$` (end of path).
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+
+    let mut rest = match path.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let parts: Vec<&str> = parts.collect();
+
+    for (i, part) in parts.iter().enumerate() {
+        // The last part of anchored pattern has to match at the end.
+        if anchored && i == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Robots;
+
+    #[test]
+    fn rules_for_agent() {
+        let txt = "\
+User-agent: Googlebot
+Disallow: /
+
+User-agent: *
+Disallow: /private/
+Allow: /private/public.html
+Disallow: /*.pdf$
+Crawl-delay: 2
+
+Sitemap: https://example.com/sitemap.xml
+";
+        let robots = Robots::parse(txt, "seen");
+        let allowed = |path: &str| robots.is_allowed(&path.parse().unwrap());
+
+        assert!(allowed("/docs/"));
+        assert!(!allowed("/private/notes.html"));
+        assert!(allowed("/private/public.html"));
+        assert!(!allowed("/docs/manual.pdf"));
+        assert!(allowed("/docs/manual.pdf.html"));
+        assert_eq!(robots.crawl_delay, Some(2.0));
+        assert_eq!(robots.sitemaps, vec!["https://example.com/sitemap.xml"]);
+
+        let google = Robots::parse(txt, "Googlebot");
+        assert!(!google.is_allowed(&"/docs/".parse().unwrap()));
+
+        for delay in ["inf", "NaN", "-1"] {
+            let txt = format!("User-agent: *\nCrawl-delay: {delay}\n");
+            assert_eq!(Robots::parse(&txt, "seen").crawl_delay, None);
+        }
+    }
+
+    #[test]
+    fn exact_agent() {
+        let txt = "\
+User-agent: unseen-bot
+Disallow: /
+
+User-agent: SEEN/1.0
+Disallow: /private/
+";
+        let robots = Robots::parse(txt, "seen");
+
+        assert!(robots.is_allowed(&"/docs/".parse().unwrap()));
+        assert!(!robots.is_allowed(&"/private/notes.html".parse().unwrap()));
+        assert!(Robots::parse(txt, "see").is_allowed(&"/private/".parse().unwrap()));
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local, TimeZone};
+use isahc::http::Uri;
+use mailparse::{DispositionType, MailHeaderMap, ParsedMail};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+
+/// Email message, such as a newsletter.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Email {
+    pub subject: Option<String>,
+    /// Sender as written in `From` header (name and address).
+    pub from: Option<String>,
+    /// Identifier of mailing list (`List-Id`), which the message was sent to.
+    pub list_id: Option<String>,
+    pub message_id: Option<String>,
+    /// When the message was sent.
+    pub date: Option<DateTime<Local>>,
+    /// First plain text part of the message.
+    pub text: Option<String>,
+    /// First HTML part of the message.
+    pub html: Option<String>,
+    /// URL from which the message was obtained.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum EmailError {
+    #[error("Could not parse email message.")]
+    Parse(#[from] mailparse::MailParseError),
+}
+
+/// Turn raw message (RFC 5322, as in .eml files) into an [`Email`].
+pub fn make_email(url: Uri, body: &[u8]) -> Result<Email, EmailError> {
+    let mail = mailparse::parse_mail(body)?;
+    let headers = mail.get_headers();
+
+    let header = |name: &str| {
+        headers
+            .get_first_value(name)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    let date = header("Date")
+        .and_then(|d| mailparse::dateparse(&d).ok())
+        .and_then(|d| Local.timestamp_opt(d, 0).single());
+
+    let mut text = None;
+    let mut html = None;
+    find_bodies(&mail, &mut text, &mut html);
+
+    Ok(Email {
+        subject: header("Subject"),
+        from: header("From"),
+        list_id: header("List-Id"),
+        message_id: header("Message-Id"),
+        date,
+        text,
+        html,
+        url,
+    })
+}
+
+/// Find first plain text and first HTML part of `mail`, leaving out attachments.
+fn find_bodies(mail: &ParsedMail, text: &mut Option<String>, html: &mut Option<String>) {
+    if mail.get_content_disposition().disposition == DispositionType::Attachment {
+        return;
+    }
+
+    if !mail.subparts.is_empty() {
+        for part in &mail.subparts {
+            find_bodies(part, text, html);
+        }
+        return;
+    }
+
+    let target = match mail.ctype.mimetype.as_str() {
+        "text/plain" => text,
+        "text/html" => html,
+        _ => return,
+    };
+
+    if target.is_none() {
+        *target = mail.get_body().ok().filter(|b| !b.trim().is_empty());
+    }
+}
+
+impl Prepare for Email {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        options: &SeenOptions,
+        preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        let content = match (&self.html, &self.text) {
+            (Some(html), _) => {
+                let extract = crate::options::extract(options, preferences);
+                let readable = extract.as_ref().extract(html);
+                let md =
+                    futures::executor::block_on(crate::convert::md::html_to_md(&readable.content))
+                        .ok();
+
+                Content::WebPage {
+                    text: readable.text,
+                    rich_text: md,
+                }
+            }
+            (None, text) => Content::Text {
+                text: text.clone().unwrap_or_default(),
+                rich_text: None,
+            },
+        };
+
+        if let Some(from) = &self.from {
+            metadata.insert("from".to_string(), serde_json::to_value(from).unwrap());
+        }
+
+        if let Some(list_id) = &self.list_id {
+            metadata.insert(
+                "list_id".to_string(),
+                serde_json::to_value(list_id).unwrap(),
+            );
+        }
+
+        if let Some(date) = &self.date {
+            metadata.insert(
+                "date".to_string(),
+                serde_json::to_value(date.to_rfc3339()).unwrap(),
+            );
+        }
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        Document {
+            title: self
+                .subject
+                .clone()
+                .unwrap_or_else(|| "(no subject)".to_string()),
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content,
+            metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::make_email;
+
+    #[test]
+    fn newsletter_parts() {
+        let message = "\
+From: Weekly News <news@example.com>\r
+To: me@example.com\r
+Subject: =?utf-8?q?Issue_=231=3A_Caf=C3=A9?=\r
+List-Id: Weekly News <weekly.example.com>\r
+Date: Tue, 14 Mar 2023 10:00:00 +0000\r
+Message-ID: <issue-1@example.com>\r
+MIME-Version: 1.0\r
+Content-Type: multipart/mixed; boundary=\"outer\"\r
+\r
+--outer\r
+Content-Type: multipart/alternative; boundary=\"inner\"\r
+\r
+--inner\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Hello reader.\r
+--inner\r
+Content-Type: text/html; charset=utf-8\r
+\r
+<p>Hello <b>reader</b>.</p>\r
+--inner--\r
+--outer\r
+Content-Type: text/plain\r
+Content-Disposition: attachment; filename=\"notes.txt\"\r
+\r
+Not the body.\r
+--outer--\r
+";
+
+        let email = make_email(
+            "mid:issue-1@example.com".parse().unwrap(),
+            message.as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(email.subject.as_deref(), Some("Issue #1: Café"));
+        assert_eq!(
+            email.from.as_deref(),
+            Some("Weekly News <news@example.com>")
+        );
+        assert_eq!(
+            email.list_id.as_deref(),
+            Some("Weekly News <weekly.example.com>")
+        );
+        assert_eq!(email.message_id.as_deref(), Some("<issue-1@example.com>"));
+        assert_eq!(email.date.unwrap().timestamp(), 1678788000);
+        assert_eq!(email.text.as_deref().map(str::trim), Some("Hello reader."));
+        assert_eq!(
+            email.html.as_deref().map(str::trim),
+            Some("<p>Hello <b>reader</b>.</p>")
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+use chrono::{DateTime, Local};
+use isahc::http::{HeaderMap, Uri};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+use zip::ZipArchive;
+
+use super::file_name;
+use super::page::decode;
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+
+/// Downloaded EPUB book.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Epub {
+    /// HTTP headers with which the book was returned.
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+    /// Raw content of the EPUB file.
+    #[serde(with = "crate::source::base64_serde")]
+    pub body: Vec<u8>,
+    /// URL from which the book was returned.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+}
+
+/// One chapter of a book, i. e. one content document of its spine.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Chapter {
+    /// Title of the chapter, if it has any.
+    pub title: Option<String>,
+    /// Text of the chapter without any markup.
+    pub text: String,
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum EpubError {
+    #[error("Could not unpack EPUB container.")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[error("Could not read file in EPUB container.")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid EPUB: {0}")]
+    Invalid(String),
+}
+
+/// Turn downloaded content into an [`Epub`], checking that it can be read.
+pub fn make_epub(url: Uri, headers: HeaderMap, body: Vec<u8>) -> Result<Epub, EpubError> {
+    let epub = Epub { headers, body, url };
+    epub.extract()?;
+    Ok(epub)
+}
+
+/// Metadata and content documents read from an EPUB file.
+#[derive(Debug, Default, PartialEq)]
+struct Book {
+    title: Option<String>,
+    authors: Vec<String>,
+    language: Option<String>,
+    /// Paths of content documents within the container, in spine order.
+    spine: Vec<String>,
+}
+
+impl Epub {
+    /// Read metadata of the book and HTML of its chapters in reading order.
+    fn extract(&self) -> Result<(Book, Vec<String>), EpubError> {
+        let mut zip = ZipArchive::new(Cursor::new(self.body.as_slice()))?;
+
+        let container = read_text(&mut zip, "META-INF/container.xml")?;
+        let opf_path = rootfile(&container)?;
+        let book = parse_opf(&read_text(&mut zip, &opf_path)?, &opf_path)?;
+
+        // A chapter missing in the container is left out, rest of the book
+        // is still worth reading. Chapters are decoded like webpages, they
+        // need not be UTF-8.
+        let chapters = book
+            .spine
+            .iter()
+            .filter_map(|path| read_entry(&mut zip, path).ok())
+            .map(|content| decode(&self.url, &HeaderMap::new(), &content).0)
+            .collect();
+
+        Ok((book, chapters))
+    }
+}
+
+fn read_entry(zip: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> Result<Vec<u8>, EpubError> {
+    let mut content = vec![];
+    zip.by_name(path)?.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn read_text(zip: &mut ZipArchive<Cursor<&[u8]>>, path: &str) -> Result<String, EpubError> {
+    let mut content = String::new();
+    zip.by_name(path)?.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Path of the package document (OPF) given in `META-INF/container.xml`.
+fn rootfile(container: &str) -> Result<String, EpubError> {
+    let document =
+        roxmltree::Document::parse(container).map_err(|e| EpubError::Invalid(e.to_string()))?;
+
+    document
+        .descendants()
+        .find(|n| n.tag_name().name() == "rootfile")
+        .and_then(|n| n.attribute("full-path"))
+        .map(|p| p.to_string())
+        .ok_or_else(|| EpubError::Invalid("missing rootfile".to_string()))
+}
+
+/// Read metadata and spine from package document at `opf_path`. Paths
+/// of content documents are resolved against the package document.
+fn parse_opf(opf: &str, opf_path: &str) -> Result<Book, EpubError> {
+    let document =
+        roxmltree::Document::parse(opf).map_err(|e| EpubError::Invalid(e.to_string()))?;
+    let package = document.root_element();
+
+    let texts = |name: &str| {
+        child(package, "metadata")
+            .into_iter()
+            .flat_map(|m| m.children())
+            .filter(|c| c.tag_name().name() == name)
+            .filter_map(|c| c.text())
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let manifest = child(package, "manifest")
+        .into_iter()
+        .flat_map(|m| m.children())
+        .filter(|c| c.tag_name().name() == "item")
+        .filter_map(|c| Some((c.attribute("id")?, c.attribute("href")?)))
+        .collect::<HashMap<_, _>>();
+
+    // Hrefs are relative URLs, possibly percent-encoded.
+    let base = url::Url::parse("file:///")
+        .and_then(|u| u.join(opf_path))
+        .map_err(|e| EpubError::Invalid(e.to_string()))?;
+
+    let spine = child(package, "spine")
+        .ok_or_else(|| EpubError::Invalid("missing spine".to_string()))?
+        .children()
+        .filter(|c| c.tag_name().name() == "itemref")
+        .filter(|c| c.attribute("linear") != Some("no"))
+        .filter_map(|c| manifest.get(c.attribute("idref")?))
+        .filter_map(|href| base.join(href).ok()?.to_file_path().ok())
+        .map(|p| p.to_string_lossy().trim_start_matches('/').to_string())
+        .collect();
+
+    Ok(Book {
+        title: texts("title").into_iter().next(),
+        authors: texts("creator"),
+        language: texts("language").into_iter().next(),
+        spine,
+    })
+}
+
+/// First child element of `node` with local `name`.
+fn child<'a, 'i>(node: roxmltree::Node<'a, 'i>, name: &str) -> Option<roxmltree::Node<'a, 'i>> {
+    node.children().find(|c| c.tag_name().name() == name)
+}
+
+impl Prepare for Epub {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        options: &SeenOptions,
+        preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        // Books are checked by `make_epub`, only one archived before could
+        // be unreadable. It is still worth remembering by its URL.
+        let (book, html) = self.extract().unwrap_or_default();
+
+        let extract = crate::options::extract(options, preferences);
+
+        let chapters: Vec<Chapter> = html
+            .iter()
+            .map(|html| extract.as_ref().extract(html))
+            .map(|readable| Chapter {
+                title: readable.title.filter(|t| !t.trim().is_empty()),
+                text: readable.text.trim().to_string(),
+            })
+            .filter(|c| !c.text.is_empty())
+            .collect();
+
+        let title = book
+            .title
+            .or_else(|| file_name(&self.url))
+            .unwrap_or_else(|| self.url.to_string());
+
+        if let Some(host) = self.url.host() {
+            metadata.insert("host".to_string(), serde_json::to_value(host).unwrap());
+        }
+
+        if !book.authors.is_empty() {
+            metadata.insert(
+                "author".to_string(),
+                serde_json::to_value(&book.authors).unwrap(),
+            );
+        }
+
+        if let Some(language) = book.language {
+            metadata.insert(
+                "language".to_string(),
+                serde_json::to_value(language).unwrap(),
+            );
+        }
+
+        metadata.insert(
+            "chapters".to_string(),
+            serde_json::to_value(chapters.len()).unwrap(),
+        );
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        Document {
+            title,
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content: Content::Book {
+                text: chapters
+                    .iter()
+                    .map(|c| c.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n"),
+                chapters,
+            },
+            metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use isahc::http::HeaderMap;
+    use zip::write::{FileOptions, ZipWriter};
+
+    use super::{make_epub, parse_opf, rootfile, Book};
+
+    #[test]
+    fn legacy_encoded_chapter() {
+        let mut zip = ZipWriter::new(Cursor::new(vec![]));
+        let mut add = |path: &str, content: &[u8]| {
+            zip.start_file(path, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        };
+
+        add(
+            "META-INF/container.xml",
+            br#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#,
+        );
+        add(
+            "content.opf",
+            br#"<package><manifest><item id="ch1" href="ch1.html"/></manifest><spine><itemref idref="ch1"/></spine></package>"#,
+        );
+        add(
+            "ch1.html",
+            b"<html><head><meta charset=\"windows-1252\"></head><body>Caf\xe9</body></html>",
+        );
+
+        let body = zip.finish().unwrap().into_inner();
+        let url = "https://example.com/book.epub".parse().unwrap();
+        let (_, chapters) = make_epub(url, HeaderMap::new(), body)
+            .unwrap()
+            .extract()
+            .unwrap();
+
+        assert_eq!(chapters.len(), 1);
+        assert!(chapters[0].contains("Café"));
+
+        let url = "https://example.com/book.epub".parse().unwrap();
+        assert!(make_epub(url, HeaderMap::new(), b"not a book".to_vec()).is_err());
+    }
+
+    #[test]
+    fn package_document() {
+        let container = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+
+        let opf = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="id">urn:isbn:0000000000</dc:identifier>
+    <dc:title>The Book</dc:title>
+    <dc:creator>First Author</dc:creator>
+    <dc:creator>Second Author</dc:creator>
+    <dc:language>en</dc:language>
+  </metadata>
+  <manifest>
+    <item id="cover" href="Text/cover.xhtml" media-type="application/xhtml+xml"/>
+    <item id="ch1" href="Text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+    <item id="ch2" href="../chapter2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="css" href="style.css" media-type="text/css"/>
+  </manifest>
+  <spine>
+    <itemref idref="cover" linear="no"/>
+    <itemref idref="ch1"/>
+    <itemref idref="ch2"/>
+  </spine>
+</package>"#;
+
+        let opf_path = rootfile(container).unwrap();
+        assert_eq!(opf_path, "OEBPS/content.opf");
+
+        assert_eq!(
+            parse_opf(opf, &opf_path).unwrap(),
+            Book {
+                title: Some("The Book".to_string()),
+                authors: vec!["First Author".to_string(), "Second Author".to_string()],
+                language: Some("en".to_string()),
+                spine: vec![
+                    "OEBPS/Text/chapter 1.xhtml".to_string(),
+                    "chapter2.xhtml".to_string()
+                ],
+            }
+        );
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use chrono::{DateTime, Local};
+use isahc::http::header::CONTENT_TYPE;
+use isahc::http::{HeaderMap, Uri};
+use kuchiki::traits::TendrilSink;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::page::decode;
+use super::{file_name, find_bytes};
+use crate::batch::describe;
+use crate::convert::ocr::image_to_text;
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::job::{self, JobError};
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+use crate::Seen;
+
+/// Downloaded image.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Image {
+    /// HTTP headers with which the image was returned.
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+    /// Raw content of the image.
+    #[serde(with = "crate::source::base64_serde")]
+    pub body: Vec<u8>,
+    /// URL from which the image was returned.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+    /// Alternative text of the image found on the referring page.
+    pub alt: Option<String>,
+    /// Text recognized in the image, if OCR is configured.
+    #[serde(default)]
+    pub ocr: Option<String>,
+    /// Why text could not be recognized, if OCR failed.
+    #[serde(default)]
+    pub ocr_error: Option<String>,
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum ImageError {
+    #[error("Could not download referring page.")]
+    Referrer(#[from] Box<JobError>),
+}
+
+/// Turn downloaded content into an [`Image`]. If `referrer` is given, the page
+/// is downloaded like sources (see [`job::fetch`]) and searched for alternative
+/// text of the image. If `ocr_command` is given, text in the image is recognized
+/// by it. Failed recognition is recorded, the image is still worth indexing.
+pub async fn make_image(
+    seen: &Seen,
+    url: Uri,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    referrer: Option<&Uri>,
+    ocr_command: Option<&[String]>,
+) -> Result<Image, ImageError> {
+    let alt = match referrer {
+        Some(referrer) => {
+            let (page_url, page_headers, page) =
+                job::fetch(seen, referrer).await.map_err(Box::new)?;
+            let (html, _) = decode(&page_url, &page_headers, &page);
+            alt_text(&html, &page_url, &url)
+        }
+        None => None,
+    };
+
+    let (ocr, ocr_error) = match ocr_command {
+        Some(command) => match image_to_text(command, &body).await {
+            Ok(text) => (
+                Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+                None,
+            ),
+            Err(e) => (None, Some(describe(&e))),
+        },
+        None => (None, None),
+    };
+
+    Ok(Image {
+        headers,
+        body,
+        url,
+        alt,
+        ocr,
+        ocr_error,
+    })
+}
+
+impl Prepare for Image {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        _options: &SeenOptions,
+        _preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        let exif = exif_fields(&self.body);
+        let xmp = xmp_fields(&self.body);
+
+        let xmp_text = |key: &str| {
+            xmp.get(key).and_then(|v| v.as_array()).map(|v| {
+                v.iter()
+                    .filter_map(|s| s.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+        };
+
+        let title = self
+            .alt
+            .clone()
+            .or_else(|| xmp_text("title"))
+            .or_else(|| file_name(&self.url))
+            .unwrap_or_else(|| self.url.to_string());
+
+        // Everything textual that describes the image is its content.
+        let text = [
+            self.alt.clone(),
+            xmp_text("description"),
+            exif.get("ImageDescription")
+                .and_then(|d| d.as_str())
+                .map(|d| d.to_string()),
+            self.ocr.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+        if let Some(host) = self.url.host() {
+            metadata.insert("host".to_string(), serde_json::to_value(host).unwrap());
+        }
+
+        if let Some(alt) = &self.alt {
+            metadata.insert("alt".to_string(), serde_json::to_value(alt).unwrap());
+        }
+
+        if !exif.is_empty() {
+            metadata.insert("exif".to_string(), serde_json::to_value(exif).unwrap());
+        }
+
+        if !xmp.is_empty() {
+            metadata.insert("xmp".to_string(), serde_json::to_value(xmp).unwrap());
+        }
+
+        if let Some(error) = &self.ocr_error {
+            metadata.insert(
+                "ocr_error".to_string(),
+                serde_json::to_value(error).unwrap(),
+            );
+        }
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        let mime = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+
+        Document {
+            title,
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content: Content::Image {
+                text,
+                mime,
+                data: self.body.clone(),
+            },
+            metadata,
+        }
+    }
+}
+
+/// Find alternative text of image `image` on HTML page `page` located at `base`.
+fn alt_text(page: &str, base: &Uri, image: &Uri) -> Option<String> {
+    let base = url::Url::parse(&base.to_string()).ok()?;
+    let image = image.to_string();
+
+    let document = kuchiki::parse_html().one(page);
+
+    let alt = document
+        .select("img")
+        .ok()?
+        .find(|img| {
+            img.attributes
+                .borrow()
+                .get("src")
+                .and_then(|src| base.join(src).ok())
+                .map(|src| src.as_str() == image)
+                .unwrap_or(false)
+        })
+        .and_then(|img| {
+            img.attributes
+                .borrow()
+                .get("alt")
+                .map(|alt| alt.trim().to_string())
+        })
+        .filter(|alt| !alt.is_empty());
+
+    alt
+}
+
+/// Read EXIF tags of the primary image. Tags with binary values or
+/// with overly long values are left out.
+fn exif_fields(body: &[u8]) -> HashMap<String, Value> {
+    let exif = match exif::Reader::new().read_from_container(&mut Cursor::new(body)) {
+        Ok(exif) => exif,
+        Err(_) => return HashMap::new(),
+    };
+
+    exif.fields()
+        .filter(|f| f.ifd_num == exif::In::PRIMARY)
+        .filter(|f| !matches!(f.value, exif::Value::Undefined(..)))
+        .map(|f| {
+            (
+                f.tag.to_string(),
+                f.display_value().with_unit(&exif).to_string(),
+            )
+        })
+        .filter(|(_, v)| !v.is_empty() && v.len() <= 200)
+        .map(|(k, v)| (k, Value::String(v.trim_matches('"').to_string())))
+        .collect()
+}
+
+const XMP_START: &[u8] = b"<x:xmpmeta";
+const XMP_END: &[u8] = b"</x:xmpmeta>";
+const DUBLIN_CORE: &str = "http://purl.org/dc/elements/1.1/";
+
+/// Read Dublin Core properties (title, description, subject…) from XMP packet
+/// embedded in the image, if there is any.
+fn xmp_fields(body: &[u8]) -> HashMap<String, Value> {
+    let packet = match (find_bytes(body, XMP_START), find_bytes(body, XMP_END)) {
+        (Some(s), Some(e)) if s < e => &body[s..e + XMP_END.len()],
+        _ => return HashMap::new(),
+    };
+
+    let packet = match std::str::from_utf8(packet) {
+        Ok(packet) => packet,
+        Err(_) => return HashMap::new(),
+    };
+
+    let xmp = match roxmltree::Document::parse(packet) {
+        Ok(xmp) => xmp,
+        Err(_) => return HashMap::new(),
+    };
+
+    xmp.descendants()
+        .filter(|n| n.tag_name().namespace() == Some(DUBLIN_CORE))
+        .filter_map(|n| {
+            let values = n
+                .descendants()
+                .filter(|t| t.is_text())
+                .filter_map(|t| t.text())
+                .map(|t| t.trim())
+                .filter(|t| !t.is_empty())
+                .map(|t| Value::String(t.to_string()))
+                .collect::<Vec<_>>();
+
+            (!values.is_empty()).then(|| (n.tag_name().name().to_string(), Value::Array(values)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use isahc::http::HeaderMap;
+
+    use super::{alt_text, exif_fields, make_image, xmp_fields};
+    use crate::testing::{response, serve};
+    use crate::Seen;
+
+    #[tokio::test]
+    async fn referrer_and_failed_ocr() {
+        let address = serve(|path| match path {
+            "/gallery" => response(
+                "200 OK",
+                &[("Content-Type", "text/html")],
+                r#"<img src="/cat.png" alt="A cat">"#,
+            ),
+            _ => response(
+                "404 Not Found",
+                &[("Content-Type", "text/html")],
+                "<img src=\"/cat.png\" alt=\"Not found\">",
+            ),
+        })
+        .await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let seen = Seen::for_test(dir.path()).await;
+        let url = |path: &str| format!("http://{address}{path}").parse().unwrap();
+        let failing_ocr = ["false".to_string()];
+
+        let image = make_image(
+            &seen,
+            url("/cat.png"),
+            HeaderMap::new(),
+            vec![],
+            Some(&url("/gallery")),
+            Some(&failing_ocr),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(image.alt.as_deref(), Some("A cat"));
+        assert_eq!(image.ocr, None);
+        assert!(image.ocr_error.is_some());
+
+        let missing = make_image(
+            &seen,
+            url("/cat.png"),
+            HeaderMap::new(),
+            vec![],
+            Some(&url("/missing")),
+            None,
+        )
+        .await;
+
+        assert!(missing.is_err());
+    }
+
+    /// JPEG with EXIF segment holding only `ImageDescription` (and no image data).
+    fn jpeg_with_description(description: &str) -> Vec<u8> {
+        let value = [description.as_bytes(), b"\0"].concat();
+
+        // Little-endian TIFF header, one IFD with one ASCII entry, then its value.
+        let mut tiff = b"II*\0\x08\0\0\0\x01\0\x0e\x01\x02\0".to_vec();
+        tiff.extend((value.len() as u32).to_le_bytes());
+        tiff.extend(26u32.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        tiff.extend(&value);
+
+        let app1 = [b"Exif\0\0".as_slice(), &tiff].concat();
+
+        let mut jpeg = b"\xff\xd8\xff\xe1".to_vec();
+        jpeg.extend((app1.len() as u16 + 2).to_be_bytes());
+        jpeg.extend(app1);
+        jpeg.extend(b"\xff\xd9");
+        jpeg
+    }
+
+    #[test]
+    fn alt_text_of_image() {
+        let page = r#"<html><body>
+            <img src="/logo.png" alt="Logo">
+            <img src="photos/cat.jpg" alt="  A cat on a roof ">
+            <img src="empty.jpg" alt="">
+        </body></html>"#;
+        let base = "https://example.com/blog/post".parse().unwrap();
+        let alt = |image: &str| alt_text(page, &base, &image.parse().unwrap());
+
+        assert_eq!(
+            alt("https://example.com/blog/photos/cat.jpg").as_deref(),
+            Some("A cat on a roof")
+        );
+        assert_eq!(alt("https://example.com/logo.png").as_deref(), Some("Logo"));
+        assert_eq!(alt("https://example.com/blog/empty.jpg"), None);
+        assert_eq!(alt("https://example.com/other.jpg"), None);
+    }
+
+    #[test]
+    fn exif_description() {
+        let exif = exif_fields(&jpeg_with_description("Sunset over the lake"));
+
+        assert_eq!(
+            exif.get("ImageDescription").and_then(|d| d.as_str()),
+            Some("Sunset over the lake")
+        );
+        assert!(exif_fields(b"not an image").is_empty());
+    }
+
+    #[test]
+    fn xmp_dublin_core() {
+        let image = [
+            b"\xff\xd8 binary data ".as_slice(),
+            br#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+              <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+                <rdf:Description xmlns:dc="http://purl.org/dc/elements/1.1/">
+                  <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Lake</rdf:li></rdf:Alt></dc:title>
+                  <dc:subject><rdf:Bag><rdf:li>sunset</rdf:li><rdf:li>lake</rdf:li></rdf:Bag></dc:subject>
+                </rdf:Description>
+              </rdf:RDF>
+            </x:xmpmeta>"#,
+            b" more binary data \xff\xd9",
+        ]
+        .concat();
+
+        let xmp = xmp_fields(&image);
+
+        assert_eq!(xmp.get("title"), Some(&serde_json::json!(["Lake"])));
+        assert_eq!(
+            xmp.get("subject"),
+            Some(&serde_json::json!(["sunset", "lake"]))
+        );
+        assert!(xmp_fields(b"no metadata").is_empty());
+    }
+}
@@ -1,14 +1,27 @@
+pub mod email;
+pub mod epub;
+pub mod image;
+pub mod notebook;
 pub mod page;
+pub mod pdf;
+pub mod text;
 pub mod video;
 
 use std::collections::HashMap;
 
 use chrono::{DateTime, Local};
+pub use email::{make_email, Email, EmailError};
+pub use epub::{make_epub, Chapter, Epub, EpubError};
+pub use image::{make_image, Image, ImageError};
 use isahc::http::Uri;
-use mime::{Mime, HTML, IMAGE, PNG, TEXT, VIDEO};
-pub use page::{make_page, Page, PageError};
+use mime::{Mime, APPLICATION, HTML, IMAGE, MESSAGE, PDF, PLAIN, TEXT, VIDEO};
+pub use notebook::{is_notebook_url, make_notebook, Notebook};
+pub use page::{make_page, Page};
+pub use pdf::{make_pdf, Pdf, PdfError};
 use serde::Serialize;
 use serde_json::Value;
+pub use text::{make_text, Text};
+pub use video::VideoError;
 
 use self::video::Video;
 use crate::document::*;
@@ -20,7 +33,13 @@ use crate::url_preferences::Preferences;
 /// Ideally the source would contain everything
 #[derive(Clone, Debug, Serialize)]
 pub enum Source {
+    Email(Email),
+    Epub(Epub),
+    Image(Image),
+    Notebook(Notebook),
     Page(Page),
+    Pdf(Pdf),
+    Text(Text),
     Video(Video),
 }
 
@@ -33,24 +52,43 @@ impl Prepare for Source {
         time: DateTime<Local>,
     ) -> Document {
         match self {
+            Source::Email(email) => email.prepare_document(metadata, options, preferences, time),
+            Source::Epub(epub) => epub.prepare_document(metadata, options, preferences, time),
+            Source::Image(image) => image.prepare_document(metadata, options, preferences, time),
+            Source::Notebook(notebook) => {
+                notebook.prepare_document(metadata, options, preferences, time)
+            }
             Source::Page(page) => page.prepare_document(metadata, options, preferences, time),
-            Source::Video(_) => todo!(),
+            Source::Pdf(pdf) => pdf.prepare_document(metadata, options, preferences, time),
+            Source::Text(text) => text.prepare_document(metadata, options, preferences, time),
+            Source::Video(video) => video.prepare_document(metadata, options, preferences, time),
         }
     }
 }
 impl Source {
     pub fn url(&self) -> Option<&Uri> {
         match self {
+            Source::Email(e) => Some(&e.url),
+            Source::Epub(e) => Some(&e.url),
+            Source::Image(i) => Some(&i.url),
+            Source::Notebook(n) => Some(&n.url),
             Source::Page(p) => Some(&p.url),
-            Source::Video(v) => Some(&v.url), //v.url.as_ref(),
+            Source::Pdf(p) => Some(&p.url),
+            Source::Text(t) => Some(&t.url),
+            Source::Video(v) => Some(&v.url),
         }
     }
 }
 
 /// All supported types of documents.
 pub enum SourceType {
+    Email,
+    Epub,
     Image,
+    Notebook,
     Page,
+    Pdf,
+    Text,
     Video,
 }
 
@@ -60,9 +98,50 @@ impl SourceType {
     pub fn from_mime(mime: &Mime) -> Option<SourceType> {
         match (mime.type_(), mime.subtype()) {
             (TEXT, HTML) => Some(SourceType::Page),
-            (IMAGE, PNG) => Some(SourceType::Image),
+            (TEXT, PLAIN) => Some(SourceType::Text),
+            _ if text::is_markdown_mime(mime) => Some(SourceType::Text),
+            (APPLICATION, PDF) => Some(SourceType::Pdf),
+            (APPLICATION, sub) if sub == "epub+zip" => Some(SourceType::Epub),
+            (APPLICATION, sub) if sub == "x-ipynb+json" => Some(SourceType::Notebook),
+            (IMAGE, _) => Some(SourceType::Image),
             (VIDEO, _) => Some(SourceType::Video),
+            (MESSAGE, sub) if sub == "rfc822" => Some(SourceType::Email),
+            (TEXT, sub) if sub == "vtt" => Some(SourceType::Video),
+            (APPLICATION, sub) if sub == "x-subrip" => Some(SourceType::Video),
             _ => None,
         }
     }
 }
+
+/// Position of the first occurrence of `needle` in `haystack`.
+pub(crate) fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Last non-empty segment of path of `url`, typically name of a file.
+pub(crate) fn file_name(url: &Uri) -> Option<String> {
+    url.path()
+        .rsplit('/')
+        .find(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+/// Binary content is archived as base64 string rather than array of numbers.
+pub(crate) mod base64_serde {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(d: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(d)?;
+        base64::decode(s).map_err(serde::de::Error::custom)
+    }
+}
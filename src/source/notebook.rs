@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use isahc::http::Uri;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::file_name;
+use super::text::markdown_heading;
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+
+/// Jupyter notebook.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Notebook {
+    /// Programming language of code cells.
+    pub language: Option<String>,
+    /// Cells of the notebook, in order.
+    pub cells: Vec<Cell>,
+    /// URL from which the notebook was returned.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+}
+
+/// One cell of a notebook.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Cell {
+    pub kind: CellKind,
+    /// Content of the cell (Markdown or code).
+    pub source: String,
+    /// Textual outputs of a code cell.
+    pub outputs: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CellKind {
+    Markdown,
+    Code,
+    Raw,
+}
+
+/// Turn downloaded content (nbformat 4 JSON) into a [`Notebook`].
+pub fn make_notebook(url: Uri, body: &[u8]) -> Result<Notebook, serde_json::Error> {
+    let raw: RawNotebook = serde_json::from_slice(body)?;
+
+    let language = raw
+        .metadata
+        .language_info
+        .and_then(|l| l.name)
+        .or_else(|| raw.metadata.kernelspec.and_then(|k| k.language));
+
+    let cells = raw
+        .cells
+        .into_iter()
+        .filter_map(|c| {
+            let kind = match c.cell_type.as_str() {
+                "markdown" => CellKind::Markdown,
+                "code" => CellKind::Code,
+                "raw" => CellKind::Raw,
+                _ => return None,
+            };
+
+            let outputs = c
+                .outputs
+                .into_iter()
+                .filter_map(RawOutput::into_text)
+                .filter(|o| !o.trim().is_empty())
+                .collect();
+
+            Some(Cell {
+                kind,
+                source: c.source.into_string(),
+                outputs,
+            })
+        })
+        .collect();
+
+    Ok(Notebook {
+        language,
+        cells,
+        url,
+    })
+}
+
+/// Whether `url` points to a notebook. Notebooks are often served as plain
+/// text or generic JSON, so their content type cannot be relied on.
+pub fn is_notebook_url(url: &Uri) -> bool {
+    url.path().to_lowercase().ends_with(".ipynb")
+}
+
+impl Notebook {
+    /// Plain text of all cells, including outputs if `outputs` is true.
+    fn text(&self, outputs: bool) -> String {
+        self.cells
+            .iter()
+            .flat_map(|c| {
+                let outputs = if outputs { c.outputs.as_slice() } else { &[] };
+                std::iter::once(&c.source).chain(outputs)
+            })
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Notebook rendered as Markdown, code cells (and their outputs if `outputs`
+    /// is true) as code blocks.
+    fn markdown(&self, outputs: bool) -> String {
+        let language = self.language.as_deref().unwrap_or("");
+
+        self.cells
+            .iter()
+            .filter(|c| !c.source.trim().is_empty())
+            .map(|c| match c.kind {
+                CellKind::Markdown => c.source.trim().to_string(),
+                CellKind::Raw => format!("```\n{}\n```", c.source.trim_end()),
+                CellKind::Code => {
+                    let mut block = format!("```{language}\n{}\n```", c.source.trim_end());
+                    if outputs {
+                        for output in &c.outputs {
+                            block.push_str(&format!("\n\n```\n{}\n```", output.trim_end()));
+                        }
+                    }
+                    block
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+impl Prepare for Notebook {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        options: &SeenOptions,
+        _preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        let title = self
+            .cells
+            .iter()
+            .filter(|c| c.kind == CellKind::Markdown)
+            .find_map(|c| markdown_heading(&c.source))
+            .or_else(|| file_name(&self.url))
+            .unwrap_or_else(|| self.url.to_string());
+
+        if let Some(host) = self.url.host() {
+            metadata.insert("host".to_string(), serde_json::to_value(host).unwrap());
+        }
+
+        if let Some(language) = &self.language {
+            metadata.insert(
+                "language".to_string(),
+                serde_json::to_value(language).unwrap(),
+            );
+        }
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        Document {
+            title,
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content: Content::Text {
+                text: self.text(options.notebook_outputs),
+                rich_text: Some(self.markdown(options.notebook_outputs)),
+            },
+            metadata,
+        }
+    }
+}
+
+/// Notebook as stored in `.ipynb` file.
+#[derive(Deserialize)]
+struct RawNotebook {
+    cells: Vec<RawCell>,
+    #[serde(default)]
+    metadata: RawMetadata,
+}
+
+#[derive(Default, Deserialize)]
+struct RawMetadata {
+    kernelspec: Option<RawKernelspec>,
+    language_info: Option<RawLanguageInfo>,
+}
+
+#[derive(Deserialize)]
+struct RawKernelspec {
+    language: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawLanguageInfo {
+    name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawCell {
+    cell_type: String,
+    #[serde(default)]
+    source: Multiline,
+    #[serde(default)]
+    outputs: Vec<RawOutput>,
+}
+
+#[derive(Deserialize)]
+struct RawOutput {
+    output_type: String,
+    /// Text of `stream` output.
+    text: Option<Multiline>,
+    /// Representations of `execute_result` and `display_data` output by content type.
+    #[serde(default)]
+    data: HashMap<String, Value>,
+    ename: Option<String>,
+    evalue: Option<String>,
+}
+
+impl RawOutput {
+    fn into_text(self) -> Option<String> {
+        match self.output_type.as_str() {
+            "stream" => self.text.map(Multiline::into_string),
+            "execute_result" | "display_data" => self
+                .data
+                .get("text/plain")
+                .and_then(|t| serde_json::from_value::<Multiline>(t.clone()).ok())
+                .map(Multiline::into_string),
+            "error" => Some(format!(
+                "{}: {}",
+                self.ename.unwrap_or_default(),
+                self.evalue.unwrap_or_default()
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Text given either as a string or as a list of lines.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Multiline {
+    One(String),
+    Lines(Vec<String>),
+}
+
+impl Default for Multiline {
+    fn default() -> Self {
+        Multiline::One(String::new())
+    }
+}
+
+impl Multiline {
+    fn into_string(self) -> String {
+        match self {
+            Multiline::One(s) => s,
+            Multiline::Lines(lines) => lines.concat(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{make_notebook, CellKind};
+
+    const NOTEBOOK: &str = r##"{
+  "cells": [
+    {"cell_type": "markdown", "metadata": {}, "source": ["# Churn analysis\n", "\n", "Monthly numbers."]},
+    {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": "print(1 + 1)",
+     "outputs": [{"output_type": "stream", "name": "stdout", "text": ["2\n"]}]},
+    {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": ["df.shape"],
+     "outputs": [{"output_type": "execute_result", "execution_count": 2, "metadata": {},
+                  "data": {"text/plain": ["(120, 4)"], "text/html": ["<b>no</b>"]}}]}
+  ],
+  "metadata": {"kernelspec": {"name": "python3", "language": "python"}},
+  "nbformat": 4,
+  "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn cells_and_outputs() {
+        let notebook = make_notebook(
+            "https://example.com/churn.ipynb".parse().unwrap(),
+            NOTEBOOK.as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(notebook.language.as_deref(), Some("python"));
+        assert_eq!(notebook.cells.len(), 3);
+        assert_eq!(notebook.cells[0].kind, CellKind::Markdown);
+        assert_eq!(
+            notebook.cells[0].source,
+            "# Churn analysis\n\nMonthly numbers."
+        );
+        assert_eq!(notebook.cells[2].outputs, vec!["(120, 4)"]);
+
+        assert_eq!(
+            notebook.text(false),
+            "# Churn analysis\n\nMonthly numbers.\n\nprint(1 + 1)\n\ndf.shape"
+        );
+        assert_eq!(
+            notebook.markdown(true),
+            "# Churn analysis\n\nMonthly numbers.\n\n```python\nprint(1 + 1)\n```\n\n```\n2\n```\n\n\
+             ```python\ndf.shape\n```\n\n```\n(120, 4)\n```"
+        );
+    }
+}
@@ -1,15 +1,16 @@
 use std::collections::HashMap;
 
+use chardetng::EncodingDetector;
 use chrono::{DateTime, Local};
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252, X_USER_DEFINED};
+use isahc::http::header::CONTENT_TYPE;
 use isahc::http::{HeaderMap, Uri};
-use isahc::prelude::*;
-use isahc::{AsyncBody, Response};
-use miette::Diagnostic;
+use mime::Mime;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use thiserror::Error;
 use uuid::Uuid;
 
+use super::file_name;
 use crate::document::*;
 use crate::fields::time_fields;
 use crate::options::SeenOptions;
@@ -21,28 +22,99 @@ pub struct Page {
     /// HTTP headers with which the webpage was returned.
     #[serde(with = "http_serde::header_map")]
     pub headers: HeaderMap,
-    /// Raw body of the webpage.
+    /// Body of the webpage, decoded from `encoding`.
     pub body: String,
     /// URL from which the webpage was returned.
     #[serde(with = "http_serde::uri")]
     pub url: Uri,
+    /// Name of character encoding in which the webpage was returned (missing
+    /// in pages archived before it was detected).
+    #[serde(default)]
+    pub encoding: Option<String>,
 }
 
-#[derive(Debug, Diagnostic, Error)]
-pub enum PageError {}
+/// Turn downloaded content into a [`Page`], decoding it to UTF-8.
+pub fn make_page(url: Uri, headers: HeaderMap, body: Vec<u8>) -> Page {
+    let (body, encoding) = decode(&url, &headers, &body);
 
-/// Turn response into a [`Page`] using given `extract`.
-pub async fn make_page(
-    mut res: Response<AsyncBody>,
-    downloaded_signal: tokio::sync::oneshot::Sender<()>,
-) -> Result<Page, PageError> {
-    let url = res.effective_uri().unwrap().clone();
-    let headers = res.headers().clone();
-    let body = res.text().await.unwrap();
+    Page {
+        headers,
+        body,
+        url,
+        encoding: Some(encoding.name().to_string()),
+    }
+}
+
+impl Page {
+    /// Encoding in which the page was returned.
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+            .as_deref()
+            .and_then(|e| Encoding::for_label(e.as_bytes()))
+            .unwrap_or(UTF_8)
+    }
+}
+
+/// Decode HTML `body` returned from `url` with `headers`. The encoding is
+/// determined by byte order mark, charset in Content-Type header, `<meta>`
+/// declaration in the document or, if none of these is present, guessed
+/// from the content; in this order.
+pub(crate) fn decode(url: &Uri, headers: &HeaderMap, body: &[u8]) -> (String, &'static Encoding) {
+    let declared = headers
+        .get(CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .and_then(|ct| ct.parse::<Mime>().ok())
+        .and_then(|m| {
+            m.get_param(mime::CHARSET)
+                .and_then(|c| Encoding::for_label(c.as_str().as_bytes()))
+        })
+        .or_else(|| meta_charset(body));
+
+    let encoding = declared.unwrap_or_else(|| {
+        // Detector expects the label in lower-case ASCII (Punycode).
+        let tld = url
+            .host()
+            .and_then(|h| h.rsplit('.').next())
+            .filter(|t| t.is_ascii())
+            .map(|t| t.to_ascii_lowercase());
+        let mut detector = EncodingDetector::new();
+        detector.feed(body, true);
+        detector.guess(tld.as_deref().map(str::as_bytes), true)
+    });
 
-    downloaded_signal.send(()).expect("AAAAA");
+    // Byte order mark takes precedence over everything else.
+    let (text, encoding, _) = encoding.decode(body);
 
-    Ok(Page { headers, body, url })
+    (text.into_owned(), encoding)
+}
+
+/// Encoding declared by `<meta charset>` or `<meta http-equiv="Content-Type">`
+/// within the first 1024 bytes of `body`.
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let start = String::from_utf8_lossy(&body[..body.len().min(1024)]).to_lowercase();
+
+    let encoding = start
+        .split("<meta")
+        .skip(1)
+        .filter_map(|tag| {
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            let charset = &tag[tag.find("charset")? + "charset".len()..];
+            let charset = charset.trim_start().strip_prefix('=')?;
+            let label = charset
+                .trim_start()
+                .trim_start_matches(['"', '\''])
+                .split(['"', '\'', ';', ' ', '/'])
+                .next()?;
+            Encoding::for_label(label.as_bytes())
+        })
+        .next()?;
+
+    // An ASCII-compatible declaration cannot describe UTF-16 content.
+    Some(match encoding {
+        e if e == UTF_16BE || e == UTF_16LE => UTF_8,
+        e if e == X_USER_DEFINED => WINDOWS_1252,
+        e => e,
+    })
 }
 
 impl Prepare for Page {
@@ -57,8 +129,7 @@ impl Prepare for Page {
 
         let extract = crate::options::extract(options, preferences);
 
-        let html =
-            webpage::HTML::from_string(self.body.clone(), Some(self.url.to_string())).unwrap();
+        let html = webpage::HTML::from_string(self.body.clone(), Some(self.url.to_string())).ok();
         let readable = extract.as_ref().extract(&self.body);
 
         let title = readable
@@ -66,13 +137,16 @@ impl Prepare for Page {
             .as_ref()
             .filter(|s| !s.is_empty())
             .or_else(|| {
-                html.opengraph
+                html.as_ref()?
+                    .opengraph
                     .properties
                     .get("title")
                     .filter(|s| !s.is_empty())
             })
-            .or_else(|| html.title.as_ref().filter(|s| !s.is_empty()))
-            .cloned();
+            .or_else(|| html.as_ref()?.title.as_ref().filter(|s| !s.is_empty()))
+            .cloned()
+            .or_else(|| file_name(&self.url))
+            .unwrap_or_else(|| self.url.to_string());
 
         // TODO: More granular
         if let Some(host) = self.url.host() {
@@ -88,7 +162,7 @@ impl Prepare for Page {
             futures::executor::block_on(crate::convert::md::html_to_md(&readable.content)).ok();
 
         Document {
-            title: title.unwrap(),
+            title,
             url: self.url.clone(),
             uuid: Uuid::new_v4(),
             time,
@@ -100,3 +174,58 @@ impl Prepare for Page {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use encoding_rs::{SHIFT_JIS, UTF_8, WINDOWS_1250};
+    use isahc::http::header::CONTENT_TYPE;
+    use isahc::http::{HeaderMap, HeaderValue, Uri};
+
+    use super::decode;
+
+    #[test]
+    fn charsets() {
+        let url: Uri = "https://example.jp/".parse().unwrap();
+        let no_headers = HeaderMap::new();
+
+        let html = "<title>日本語のページ</title><p>これは日本語のテキストです。</p>";
+        let (sjis, _, _) = SHIFT_JIS.encode(html);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=Shift_JIS"),
+        );
+        assert_eq!(decode(&url, &headers, &sjis), (html.to_string(), SHIFT_JIS));
+        assert_eq!(
+            decode(&url, &no_headers, &sjis),
+            (html.to_string(), SHIFT_JIS)
+        );
+
+        let html = r#"<meta http-equiv="Content-Type" content="text/html; charset=windows-1250"><p>Příliš žluťoučký kůň</p>"#;
+        let (cp1250, _, _) = WINDOWS_1250.encode(html);
+        assert_eq!(
+            decode(&url, &no_headers, &cp1250),
+            (html.to_string(), WINDOWS_1250)
+        );
+
+        // Byte order mark wins over the header.
+        assert_eq!(
+            decode(&url, &headers, b"\xef\xbb\xbf<p>\xc3\xa9</p>"),
+            ("<p>é</p>".to_string(), UTF_8)
+        );
+    }
+
+    #[test]
+    fn upper_case_host() {
+        let url: Uri = "https://WWW.EXAMPLE.JP/".parse().unwrap();
+        assert_eq!(url.host(), Some("WWW.EXAMPLE.JP"));
+
+        let html = "<title>日本語のページ</title><p>これは日本語のテキストです。</p>";
+        let (sjis, _, _) = SHIFT_JIS.encode(html);
+        assert_eq!(
+            decode(&url, &HeaderMap::new(), &sjis),
+            (html.to_string(), SHIFT_JIS)
+        );
+    }
+}
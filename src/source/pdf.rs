@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use isahc::http::{HeaderMap, Uri};
+use lopdf::{Document as PdfDocument, Object};
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::file_name;
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+
+/// Downloaded PDF document.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Pdf {
+    /// HTTP headers with which the document was returned.
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+    /// Raw content of the PDF file.
+    #[serde(with = "crate::source::base64_serde")]
+    pub body: Vec<u8>,
+    /// URL from which the document was returned.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum PdfError {
+    #[error("Could not parse PDF.")]
+    Parse(#[from] lopdf::Error),
+}
+
+/// Turn downloaded content into a [`Pdf`], checking that it can be read.
+pub fn make_pdf(url: Uri, headers: HeaderMap, body: Vec<u8>) -> Result<Pdf, PdfError> {
+    let pdf = Pdf { headers, body, url };
+    pdf.extract()?;
+    Ok(pdf)
+}
+
+/// Text and metadata read from a PDF file.
+#[derive(Debug)]
+struct Extracted {
+    title: Option<String>,
+    author: Option<String>,
+    /// Text of each page, in order.
+    pages: Vec<String>,
+}
+
+impl Pdf {
+    fn extract(&self) -> Result<Extracted, lopdf::Error> {
+        let pdf = PdfDocument::load_mem(&self.body)?;
+
+        let pages = pdf
+            .get_pages()
+            .into_keys()
+            .map(|n| pdf.extract_text(&[n]).unwrap_or_default())
+            .collect();
+
+        let info = pdf
+            .trailer
+            .get(b"Info")
+            .and_then(|o| match o {
+                Object::Reference(id) => pdf.get_dictionary(*id),
+                o => o.as_dict(),
+            })
+            .ok();
+
+        let info_field = |key: &[u8]| {
+            info.and_then(|i| i.get(key).ok())
+                .and_then(|o| o.as_str().ok())
+                .map(decode_text_string)
+                .filter(|s| !s.trim().is_empty())
+        };
+
+        Ok(Extracted {
+            title: info_field(b"Title"),
+            author: info_field(b"Author"),
+            pages,
+        })
+    }
+}
+
+impl Prepare for Pdf {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        _options: &SeenOptions,
+        _preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        // PDFs are checked by `make_pdf`, only one archived before could be
+        // unreadable. It is still worth remembering by its URL.
+        let extracted = self.extract().unwrap_or(Extracted {
+            title: None,
+            author: None,
+            pages: vec![],
+        });
+
+        // Title from document information, otherwise name of the file.
+        let title = extracted
+            .title
+            .or_else(|| file_name(&self.url))
+            .unwrap_or_else(|| self.url.to_string());
+
+        if let Some(host) = self.url.host() {
+            metadata.insert("host".to_string(), serde_json::to_value(host).unwrap());
+        }
+
+        if let Some(author) = extracted.author {
+            metadata.insert("author".to_string(), serde_json::to_value(author).unwrap());
+        }
+
+        metadata.insert(
+            "pages".to_string(),
+            serde_json::to_value(extracted.pages.len()).unwrap(),
+        );
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        Document {
+            title,
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content: Content::Pdf {
+                text: extracted.pages.join("\n\n"),
+                pages: extracted.pages,
+            },
+            metadata,
+        }
+    }
+}
+
+/// Decode PDF text string, which is either UTF-16BE with byte order mark
+/// or (approximately) Latin-1.
+fn decode_text_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xfe, 0xff, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => bytes.iter().map(|&b| b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use isahc::http::HeaderMap;
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Document, Object, Stream, StringFormat};
+
+    use super::{decode_text_string, make_pdf};
+
+    /// PDF with one page saying `text` and document information.
+    fn fixture(text: &str) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![100.into(), 700.into()]),
+                Operation::new("Tj", vec![Object::string_literal(text)]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::String(b"\xfe\xff\x00C\x00a\x00f\x00\xe9".to_vec(), StringFormat::Hexadecimal),
+            "Author" => Object::string_literal("Jane Doe"),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+
+        let mut body = vec![];
+        doc.save_to(&mut body).unwrap();
+        body
+    }
+
+    #[test]
+    fn text_and_metadata() {
+        let url = "https://example.com/doc.pdf".parse().unwrap();
+        let pdf = make_pdf(url, HeaderMap::new(), fixture("Hello PDF")).unwrap();
+        let extracted = pdf.extract().unwrap();
+
+        assert_eq!(extracted.title.as_deref(), Some("Café"));
+        assert_eq!(extracted.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(extracted.pages.len(), 1);
+        assert!(extracted.pages[0].contains("Hello PDF"));
+
+        let url = "https://example.com/doc.pdf".parse().unwrap();
+        assert!(make_pdf(url, HeaderMap::new(), b"not a pdf".to_vec()).is_err());
+    }
+
+    #[test]
+    fn text_strings() {
+        assert_eq!(decode_text_string(b"Caf\xe9"), "Café");
+        assert_eq!(decode_text_string(b"\xfe\xff\x00O\x00K"), "OK");
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
+use isahc::http::header::CONTENT_TYPE;
+use isahc::http::{HeaderMap, Uri};
+use mime::Mime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::file_name;
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+
+/// Plain text or Markdown document.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Text {
+    /// HTTP headers with which the text was returned.
+    #[serde(with = "http_serde::header_map")]
+    pub headers: HeaderMap,
+    /// Raw text.
+    pub body: String,
+    /// URL from which the text was returned.
+    #[serde(with = "http_serde::uri")]
+    pub url: Uri,
+}
+
+/// Turn downloaded content into a [`Text`].
+pub fn make_text(url: Uri, headers: HeaderMap, body: Vec<u8>) -> Text {
+    let body = String::from_utf8_lossy(&body).into_owned();
+
+    Text { headers, body, url }
+}
+
+impl Text {
+    /// Whether the text is Markdown, judging by its content type or,
+    /// as servers often send Markdown as plain text, by extension.
+    pub fn is_markdown(&self) -> bool {
+        let by_content_type = self
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .and_then(|ct| ct.parse::<Mime>().ok())
+            .map(|m| is_markdown_mime(&m))
+            .unwrap_or(false);
+
+        let path = self.url.path().to_lowercase();
+
+        by_content_type || path.ends_with(".md") || path.ends_with(".markdown")
+    }
+}
+
+/// Whether `mime` denotes Markdown (there is no single registered type).
+pub fn is_markdown_mime(mime: &Mime) -> bool {
+    mime.type_() == mime::TEXT && matches!(mime.subtype().as_str(), "markdown" | "x-markdown")
+}
+
+impl Prepare for Text {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        _options: &SeenOptions,
+        _preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        let markdown = self.is_markdown();
+
+        let title = if markdown {
+            markdown_heading(&self.body)
+        } else {
+            None
+        }
+        .or_else(|| first_line(&self.body))
+        .or_else(|| file_name(&self.url))
+        .unwrap_or_else(|| self.url.to_string());
+
+        if let Some(host) = self.url.host() {
+            metadata.insert("host".to_string(), serde_json::to_value(host).unwrap());
+        }
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        Document {
+            title,
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content: Content::Text {
+                text: self.body.clone(),
+                rich_text: markdown.then(|| self.body.clone()),
+            },
+            metadata,
+        }
+    }
+}
+
+/// First heading of Markdown document, either ATX (`# Title`)
+/// or setext (`Title` underlined by `===` or `---`).
+pub(crate) fn markdown_heading(body: &str) -> Option<String> {
+    let lines: Vec<&str> = body.lines().collect();
+
+    lines.iter().enumerate().find_map(|(i, line)| {
+        let trimmed = line.trim();
+
+        let after_hashes = trimmed.trim_start_matches('#');
+
+        if trimmed.starts_with('#') && after_hashes.starts_with(char::is_whitespace) {
+            let heading = after_hashes.trim().trim_end_matches('#').trim();
+            Some(heading.to_string()).filter(|h| !h.is_empty())
+        } else {
+            let underline = lines.get(i + 1).map(|l| l.trim()).unwrap_or("");
+            let is_setext = !underline.is_empty()
+                && (underline.chars().all(|c| c == '=') || underline.chars().all(|c| c == '-'));
+
+            (is_setext && !trimmed.is_empty()).then(|| trimmed.to_string())
+        }
+    })
+}
+
+/// First non-empty line of text, shortened if too long.
+fn first_line(body: &str) -> Option<String> {
+    body.lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .map(|l| match l.char_indices().nth(100) {
+            Some((i, _)) => format!("{}…", &l[..i]),
+            None => l.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{first_line, markdown_heading};
+
+    #[test]
+    fn title_from_heading() {
+        assert_eq!(
+            markdown_heading("[![badge](x)](y)\n\n## Seen ##\n\ntext"),
+            Some("Seen".to_string())
+        );
+        assert_eq!(
+            markdown_heading("\nSeen\n====\n\n# Later"),
+            Some("Seen".to_string())
+        );
+        assert_eq!(markdown_heading("#hashtag\nat all"), None);
+        assert_eq!(
+            first_line("\n\n  Network Working Group  \n"),
+            Some("Network Working Group".to_string())
+        );
+    }
+}
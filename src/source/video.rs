@@ -1,10 +1,367 @@
-use isahc::http::Uri;
-use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(Clone, Debug, Serialize)]
+use chrono::{DateTime, Local};
+use isahc::http::header::CONTENT_TYPE;
+use isahc::http::{HeaderMap, StatusCode, Uri};
+use isahc::prelude::*;
+use isahc::HttpClient;
+use miette::Diagnostic;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use uuid::Uuid;
+
+use super::file_name;
+use crate::document::*;
+use crate::fields::time_fields;
+use crate::options::SeenOptions;
+use crate::url_preferences::Preferences;
+
+/// Video represented by the transcript of its speech.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Video {
-    pub transcript: String,
+    pub transcript: Vec<Cue>,
     pub title: String,
-    #[serde(serialize_with = "http_serde::uri::serialize")]
+    #[serde(with = "http_serde::uri")]
     pub url: Uri,
 }
+
+/// One cue of a transcript, i. e. a piece of text shown in the given time span.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Cue {
+    /// Start of the cue in milliseconds from the beginning of the video.
+    pub start: u64,
+    /// End of the cue in milliseconds from the beginning of the video.
+    pub end: u64,
+    /// Text of the cue without any markup.
+    pub text: String,
+}
+
+/// Where to obtain transcript of a video from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum TranscriptLocation {
+    Url(#[serde(with = "http_serde::uri")] Uri),
+    File(PathBuf),
+}
+
+impl FromStr for TranscriptLocation {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Uri>() {
+            Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) => {
+                Ok(TranscriptLocation::Url(uri))
+            }
+            _ => Ok(TranscriptLocation::File(PathBuf::from(s))),
+        }
+    }
+}
+
+impl TranscriptLocation {
+    /// Download or read the transcript.
+    async fn load(&self, http_client: &HttpClient) -> Result<String, VideoError> {
+        match self {
+            TranscriptLocation::Url(uri) => {
+                let mut response = http_client.get_async(uri).await?;
+                if !response.status().is_success() {
+                    return Err(VideoError::HttpStatus {
+                        status: response.status(),
+                        url: uri.clone(),
+                    });
+                }
+                Ok(response.text().await?)
+            }
+            TranscriptLocation::File(path) => Ok(tokio::fs::read_to_string(path).await?),
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum VideoError {
+    #[error("Could not download transcript.")]
+    Http(#[from] isahc::Error),
+
+    #[error("Could not read transcript.")]
+    Io(#[from] std::io::Error),
+
+    #[error("{url} returned {status}.")]
+    HttpStatus { status: StatusCode, url: Uri },
+
+    #[error("Transcript does not contain any cues. Only WebVTT and SRT are supported.")]
+    EmptyTranscript,
+}
+
+/// Turn downloaded content into a [`Video`]. If `transcript` is given, the content
+/// is the video (or a page with the video) and the transcript is obtained from
+/// there. Otherwise the content itself is expected to be the transcript.
+pub async fn make_video(
+    http_client: &HttpClient,
+    url: Uri,
+    headers: &HeaderMap,
+    body: Vec<u8>,
+    transcript: Option<&TranscriptLocation>,
+) -> Result<Video, VideoError> {
+    let is_html = headers
+        .get(CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .map(|ct| ct.starts_with("text/html"))
+        .unwrap_or(false);
+
+    let body = String::from_utf8_lossy(&body).into_owned();
+
+    let (title, transcript) = match transcript {
+        Some(location) => {
+            // Only a page with the video may tell us its title.
+            let title = if is_html {
+                webpage::HTML::from_string(body, Some(url.to_string()))
+                    .ok()
+                    .and_then(|html| {
+                        html.opengraph
+                            .properties
+                            .get("title")
+                            .cloned()
+                            .or(html.title)
+                    })
+                    .filter(|s| !s.is_empty())
+            } else {
+                None
+            };
+            (title, location.load(http_client).await?)
+        }
+        None => (None, body),
+    };
+
+    let transcript = parse_transcript(&transcript);
+
+    if transcript.is_empty() {
+        return Err(VideoError::EmptyTranscript);
+    }
+
+    let title = title
+        .or_else(|| file_name(&url))
+        .unwrap_or_else(|| url.to_string());
+
+    Ok(Video {
+        transcript,
+        title,
+        url,
+    })
+}
+
+impl Prepare for Video {
+    fn prepare_document(
+        &self,
+        metadata: HashMap<String, Value>,
+        _options: &SeenOptions,
+        _preferences: &Preferences,
+        time: DateTime<Local>,
+    ) -> Document {
+        let mut metadata = metadata;
+
+        if let Some(host) = self.url.host() {
+            metadata.insert("host".to_string(), serde_json::to_value(host).unwrap());
+        }
+
+        metadata.insert(
+            "indextime".to_string(),
+            serde_json::to_value(time_fields(&time)).unwrap(),
+        );
+
+        Document {
+            title: self.title.clone(),
+            url: self.url.clone(),
+            uuid: Uuid::new_v4(),
+            time,
+            content: Content::Video {
+                text: transcript_text(&self.transcript),
+                cues: self.transcript.clone(),
+            },
+            metadata,
+        }
+    }
+}
+
+/// Join text of all cues into one text. Cue `n` starts at the sum of lengths
+/// of all previous cues plus one separator for each of them.
+pub fn transcript_text(cues: &[Cue]) -> String {
+    cues.iter()
+        .map(|c| c.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Parse WebVTT or SRT transcript. Both formats consist of blocks separated by
+/// empty lines, where cue blocks contain a timing line followed by text.
+/// Blocks without timing (WebVTT header, notes, styles) are skipped.
+pub fn parse_transcript(input: &str) -> Vec<Cue> {
+    let input = input.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+
+    let mut cues: Vec<Cue> = vec![];
+
+    for block in input.split("\n\n") {
+        let mut lines = block.lines().skip_while(|l| !l.contains("-->"));
+
+        let timing = match lines.next() {
+            Some(t) => t,
+            None => continue,
+        };
+
+        let (start, end) = match parse_timing(timing) {
+            Some(t) => t,
+            None => continue,
+        };
+
+        let text = lines
+            .map(strip_markup)
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        // Automatic captions often repeat the same text in consecutive cues.
+        if text.is_empty() || cues.last().map(|c| c.text == text).unwrap_or(false) {
+            continue;
+        }
+
+        cues.push(Cue { start, end, text });
+    }
+
+    cues
+}
+
+/// Parse timing line (`00:01:02.500 --> 00:01:04.000 align:start`) into
+/// start and end in milliseconds.
+fn parse_timing(line: &str) -> Option<(u64, u64)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+/// Parse timestamp in form `hh:mm:ss.ttt`, `mm:ss.ttt` (WebVTT)
+/// or `hh:mm:ss,ttt` (SRT) into milliseconds.
+fn parse_timestamp(s: &str) -> Option<u64> {
+    let (hms, millis) = s.split_once(['.', ','])?;
+    let millis: u64 = millis.parse().ok()?;
+
+    let seconds = hms.split(':').try_fold(0, |acc, part| {
+        part.parse::<u64>().ok().map(|n| acc * 60 + n)
+    })?;
+
+    Some(seconds * 1000 + millis)
+}
+
+/// Remove tags (`<v Speaker>`, `<i>`, `<00:00:01.000>`) and the most common
+/// entities from a line of cue text.
+fn strip_markup(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut in_tag = false;
+
+    for c in line.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => result.push(c),
+            _ => {}
+        }
+    }
+
+    result
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Format milliseconds as `hh:mm:ss`.
+pub fn format_timestamp(millis: u64) -> String {
+    let seconds = millis / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use isahc::http::{HeaderMap, StatusCode};
+    use isahc::HttpClient;
+
+    use super::{make_video, parse_transcript, Cue, TranscriptLocation, VideoError};
+    use crate::testing::{response, serve};
+
+    #[tokio::test]
+    async fn missing_transcript() {
+        let address = serve(|_| {
+            response(
+                "404 Not Found",
+                &[("Content-Type", "text/html")],
+                "<p>00:00:01.000 --> 00:00:02.000 not a transcript</p>",
+            )
+        })
+        .await;
+
+        let transcript =
+            TranscriptLocation::Url(format!("http://{address}/video.vtt").parse().unwrap());
+        let error = make_video(
+            &HttpClient::new().unwrap(),
+            "https://example.com/video.mp4".parse().unwrap(),
+            &HeaderMap::new(),
+            vec![],
+            Some(&transcript),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            VideoError::HttpStatus { status, .. } if status == StatusCode::NOT_FOUND
+        ));
+    }
+
+    #[test]
+    fn parse_webvtt() {
+        let vtt = "WEBVTT\n\nNOTE comment\n\n1\n00:00:01.000 --> 00:00:04.000 align:start\n<v Bob>Hello &amp; welcome</v>\n\n01:02.500 --> 01:05.000\nsecond <i>cue</i>\nhas two lines\n";
+
+        assert_eq!(
+            parse_transcript(vtt),
+            vec![
+                Cue {
+                    start: 1000,
+                    end: 4000,
+                    text: "Hello & welcome".to_string()
+                },
+                Cue {
+                    start: 62500,
+                    end: 65000,
+                    text: "second cue has two lines".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_srt() {
+        let srt = "1\r\n01:00:00,100 --> 01:00:02,000\r\nFirst\r\n\r\n2\r\n01:00:02,000 --> 01:00:03,000\r\nFirst\r\n\r\n3\r\n01:00:03,000 --> 01:00:04,000\r\nSecond\r\n";
+
+        assert_eq!(
+            parse_transcript(srt),
+            vec![
+                Cue {
+                    start: 3600100,
+                    end: 3602000,
+                    text: "First".to_string()
+                },
+                Cue {
+                    start: 3603000,
+                    end: 3604000,
+                    text: "Second".to_string()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Helpers shared by tests.
+
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Serve HTTP on a local port and answer each request with the raw response
+/// which `respond` returns for its path. Returns address of the server.
+pub(crate) async fn serve<F>(respond: F) -> SocketAddr
+where
+    F: Fn(&str) -> String + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0; 1024];
+            let n = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]);
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            socket.write_all(respond(path).as_bytes()).await.unwrap();
+        }
+    });
+
+    address
+}
+
+/// Raw HTTP response with `status` (e. g. `200 OK`), `headers` and `body`,
+/// after which the connection is closed.
+pub(crate) fn response(status: &str, headers: &[(&str, &str)], body: &str) -> String {
+    let headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect();
+
+    format!(
+        "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
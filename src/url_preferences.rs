@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
 
 use isahc::http::Uri;
 use mime::Mime;
@@ -16,6 +17,15 @@ pub struct Preferences {
     #[serde(with = "mime_serde")]
     pub content_type: Option<Mime>,
     pub extract: Option<Extraction>,
+    /// How to download sources.
+    #[serde(default)]
+    pub download: DownloadMode,
+    /// How to make HTTP requests.
+    #[serde(default)]
+    pub request: RequestSettings,
+    /// Download even what robots.txt disallows.
+    #[serde(default)]
+    pub ignore_robots: bool,
 
     // TODO Make more sophisticated: +add -remove
     pub tags: HashSet<String>,
@@ -24,6 +34,79 @@ pub struct Preferences {
     // include time
 }
 
+/// Way of downloading a source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadMode {
+    /// Plain HTTP request.
+    #[default]
+    Plain,
+    /// Page is loaded in headless Chromium and its DOM is serialized once it
+    /// stops loading, so that content produced by JavaScript is not missed.
+    Rendered,
+}
+
+/// Customisation of HTTP requests, e. g. for sites which require logging in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestSettings {
+    /// Additional headers of requests.
+    pub headers: BTreeMap<String, String>,
+    /// Cookie file in Netscape format (as exported from browsers or written
+    /// by curl), whose cookies are sent with requests.
+    pub cookie_file: Option<PathBuf>,
+    /// User agent instead of the default one.
+    pub user_agent: Option<String>,
+    /// Authentication of requests.
+    pub auth: Option<Auth>,
+    /// How long a request may take, in seconds.
+    pub timeout: Option<u64>,
+    /// Maximum size of response body in bytes. Larger responses fail.
+    pub max_body_size: Option<u64>,
+    /// How to connect to servers instead of the default route.
+    pub route: Option<Route>,
+}
+
+/// Way of connecting to servers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Route {
+    /// Without any proxy.
+    Direct,
+    /// Through SOCKS proxy of Tor.
+    Tor,
+    /// Through HTTP or SOCKS proxy at given URL.
+    Proxy(String),
+}
+
+impl std::fmt::Display for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Route::Direct => write!(f, "direct connection"),
+            Route::Tor => write!(f, "Tor"),
+            Route::Proxy(proxy) => write!(f, "proxy {proxy}"),
+        }
+    }
+}
+
+/// HTTP authentication.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Auth {
+    Basic { username: String, password: Secret },
+    Bearer { token: Secret },
+}
+
+/// Where to find a secret, so that it does not have to be stored in preferences.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Secret {
+    /// Value of environment variable.
+    Env(String),
+    /// Password stored in system keyring.
+    Keyring { service: String, user: String },
+}
+
 /// Find preferences for `url`.
 pub async fn for_url(url: &Uri, seen: &Seen) -> Option<UrlPreferences> {
     query_as("SELECT preferences FROM url_preferences WHERE ? GLOB pattern")
@@ -41,6 +124,7 @@ struct InvalidPreferencesFormat;
 /// URL-specific preferences. These preferences are stored in database
 /// and allow users to override any default preferences.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum UrlPreferences {
     Blacklist,
     Preferences(Preferences),
@@ -93,7 +177,7 @@ mod mime_serde {
 
 #[cfg(test)]
 mod test {
-    use super::Preferences;
+    use super::{Auth, Preferences, RequestSettings, Secret};
 
     #[test]
     fn deserialize_preferences() {
@@ -101,4 +185,28 @@ mod test {
         let s = serde_json::from_str::<Preferences>(j);
         println!("{s:?}");
     }
+
+    #[test]
+    fn deserialize_request_settings() {
+        let j = r#"{
+            "headers": {"Accept-Language": "cs"},
+            "user_agent": "Mozilla/5.0",
+            "auth": {"type": "bearer", "token": {"env": "EXAMPLE_TOKEN"}},
+            "timeout": 30
+        }"#;
+        let s = serde_json::from_str::<RequestSettings>(j).unwrap();
+
+        assert_eq!(
+            s.headers.get("Accept-Language").map(|v| v.as_str()),
+            Some("cs")
+        );
+        assert_eq!(
+            s.auth,
+            Some(Auth::Bearer {
+                token: Secret::Env("EXAMPLE_TOKEN".to_string())
+            })
+        );
+        assert_eq!(s.timeout, Some(30));
+        assert_eq!(s.cookie_file, None);
+    }
 }
@@ -0,0 +1,391 @@
+use std::io::{BufRead, BufReader, Read, Write};
+
+use chrono::{DateTime, Local, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use isahc::http::header::{HeaderName, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
+use isahc::http::{HeaderMap, HeaderValue, StatusCode};
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::source::find_bytes;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum WarcError {
+    #[error("Could not read WARC file.")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid WARC record: {0}")]
+    InvalidRecord(String),
+}
+
+/// One record of a WARC file.
+#[derive(Debug)]
+pub struct Record {
+    /// Named fields of the record header (`WARC-Type`, `WARC-Target-URI`…).
+    pub headers: Vec<(String, String)>,
+    /// Content block of the record.
+    pub block: Vec<u8>,
+}
+
+impl Record {
+    /// Value of header field `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// `WARC-Date` of the record, i. e. when the content was captured.
+    pub fn date(&self) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(self.header("WARC-Date")?)
+            .ok()
+            .map(|d| d.with_timezone(&Local))
+    }
+}
+
+/// HTTP response stored in a `response` record.
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// Body with transfer and content encoding removed (as are headers
+    /// describing them).
+    pub body: Vec<u8>,
+}
+
+/// Reader of records of a WARC file, which may be compressed by gzip
+/// (either as a whole or record by record).
+pub struct WarcReader {
+    reader: Box<dyn BufRead>,
+    /// Whether the last record was invalid, so that its remains have to be
+    /// skipped before the next record.
+    resync: bool,
+}
+
+impl WarcReader {
+    pub fn new<R: Read + 'static>(reader: R) -> WarcReader {
+        let mut reader = BufReader::new(reader);
+
+        let gzipped = reader
+            .fill_buf()
+            .map(|b| b.starts_with(&[0x1f, 0x8b]))
+            .unwrap_or(false);
+
+        let reader: Box<dyn BufRead> = if gzipped {
+            Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+        } else {
+            Box::new(reader)
+        };
+
+        WarcReader {
+            reader,
+            resync: false,
+        }
+    }
+
+    fn read_record(&mut self) -> Result<Option<Record>, WarcError> {
+        // Version line, possibly preceded by empty lines ending previous record
+        // (or by anything, if the previous record was invalid).
+        let version = loop {
+            let mut line = vec![];
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim();
+            if !line.is_empty() && (!self.resync || line.starts_with("WARC/")) {
+                break line.to_string();
+            }
+        };
+
+        if !version.starts_with("WARC/") {
+            return Err(WarcError::InvalidRecord(format!(
+                "expected version, found {version:?}"
+            )));
+        }
+
+        let mut headers = vec![];
+
+        loop {
+            let mut line = vec![];
+            if self.reader.read_until(b'\n', &mut line)? == 0 {
+                return Err(WarcError::InvalidRecord("unexpected end".to_string()));
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        let length: u64 = headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case("Content-Length"))
+            .and_then(|(_, v)| v.parse().ok())
+            .ok_or_else(|| WarcError::InvalidRecord("missing Content-Length".to_string()))?;
+
+        let mut block = vec![];
+        (&mut self.reader).take(length).read_to_end(&mut block)?;
+
+        if (block.len() as u64) < length {
+            return Err(WarcError::InvalidRecord("truncated block".to_string()));
+        }
+
+        Ok(Some(Record { headers, block }))
+    }
+}
+
+impl Iterator for WarcReader {
+    type Item = Result<Record, WarcError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.read_record();
+        self.resync = matches!(record, Err(WarcError::InvalidRecord(_)));
+        record.transpose()
+    }
+}
+
+/// Writer of WARC records, optionally compressing each record by gzip
+/// (as is usual for `.warc.gz` files).
+pub struct WarcWriter<W: Write> {
+    out: W,
+    gzip: bool,
+}
+
+impl<W: Write> WarcWriter<W> {
+    pub fn new(out: W, gzip: bool) -> WarcWriter<W> {
+        WarcWriter { out, gzip }
+    }
+
+    /// Write record of type `warc_type` with additional header fields `headers`
+    /// and content `block`. Returns ID of the record.
+    pub fn write_record(
+        &mut self,
+        warc_type: &str,
+        date: DateTime<Utc>,
+        headers: &[(&str, &str)],
+        block: &[u8],
+    ) -> std::io::Result<String> {
+        let id = format!("<urn:uuid:{}>", uuid::Uuid::new_v4());
+
+        let mut record = format!(
+            "WARC/1.0\r\nWARC-Type: {warc_type}\r\nWARC-Record-ID: {id}\r\nWARC-Date: {}\r\n",
+            date.format("%Y-%m-%dT%H:%M:%SZ")
+        )
+        .into_bytes();
+
+        for (name, value) in headers {
+            record.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+        }
+
+        record.extend_from_slice(format!("Content-Length: {}\r\n\r\n", block.len()).as_bytes());
+        record.extend_from_slice(block);
+        record.extend_from_slice(b"\r\n\r\n");
+
+        if self.gzip {
+            let mut encoder = GzEncoder::new(&mut self.out, Compression::default());
+            encoder.write_all(&record)?;
+            encoder.finish()?;
+        } else {
+            self.out.write_all(&record)?;
+        }
+
+        Ok(id)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Parse HTTP response stored in block of a `response` record.
+pub fn parse_response(block: &[u8]) -> Option<HttpResponse> {
+    let end = find_bytes(block, b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&block[..end]).ok()?;
+    let body = &block[end + 4..];
+
+    let mut lines = head.split("\r\n");
+
+    let status = lines
+        .next()?
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<StatusCode>().ok())?;
+
+    let mut headers = HeaderMap::new();
+
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.trim().as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+    }
+
+    let chunked = headers
+        .get(TRANSFER_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_lowercase().contains("chunked"))
+        .unwrap_or(false);
+
+    let body = match dechunk(body).filter(|_| chunked) {
+        Some(dechunked) => {
+            headers.remove(TRANSFER_ENCODING);
+            headers.remove(CONTENT_LENGTH);
+            dechunked
+        }
+        None => body.to_vec(),
+    };
+
+    let gzipped = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.eq_ignore_ascii_case("gzip"))
+        .unwrap_or(false);
+
+    let body = if gzipped {
+        let mut decoded = vec![];
+        match MultiGzDecoder::new(body.as_slice()).read_to_end(&mut decoded) {
+            Ok(_) => {
+                headers.remove(CONTENT_ENCODING);
+                headers.remove(CONTENT_LENGTH);
+                decoded
+            }
+            Err(_) => body,
+        }
+    } else {
+        body
+    };
+
+    Some(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+/// Remove chunked transfer encoding.
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = vec![];
+
+    loop {
+        let line_end = find_bytes(data, b"\r\n")?;
+        let size = std::str::from_utf8(&data[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+
+        data = &data[line_end + 2..];
+
+        if size == 0 {
+            return Some(body);
+        }
+
+        body.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use isahc::http::header::{CONTENT_TYPE, TRANSFER_ENCODING};
+
+    use super::{parse_response, WarcReader, WarcWriter};
+
+    fn record(warc_type: &str, uri: &str, block: &str) -> String {
+        format!(
+            "WARC/1.0\r\nWARC-Type: {warc_type}\r\nWARC-Target-URI: {uri}\r\n\
+             WARC-Date: 2023-01-15T10:00:00Z\r\nContent-Length: {}\r\n\r\n{block}\r\n\r\n",
+            block.len()
+        )
+    }
+
+    #[test]
+    fn read_records() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\
+                        Transfer-Encoding: chunked\r\n\r\n5\r\n<p>Hi\r\n5\r\n</p>\n\r\n0\r\n\r\n";
+        let warc = [
+            record("warcinfo", "", "software: test\r\n"),
+            record("response", "https://example.com/", response),
+        ]
+        .concat();
+
+        let records = WarcReader::new(std::io::Cursor::new(warc.into_bytes()))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].header("warc-type"), Some("response"));
+        assert_eq!(records[1].date().unwrap().timestamp(), 1673776800);
+
+        let response = parse_response(&records[1].block).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"<p>Hi</p>\n");
+        assert!(response.headers.get(TRANSFER_ENCODING).is_none());
+        assert!(response.headers.get(CONTENT_TYPE).is_some());
+    }
+
+    #[test]
+    fn skip_invalid_record() {
+        let warc = [
+            record(
+                "response",
+                "https://example.com/1",
+                "HTTP/1.1 200 OK\r\n\r\n1",
+            ),
+            "WARC/1.0\r\nWARC-Type: response\r\n\r\nno length\r\n\r\n".to_string(),
+            record(
+                "response",
+                "https://example.com/2",
+                "HTTP/1.1 200 OK\r\n\r\n2",
+            ),
+        ]
+        .concat();
+
+        let records = WarcReader::new(std::io::Cursor::new(warc.into_bytes())).collect::<Vec<_>>();
+
+        assert_eq!(records.len(), 3);
+        assert!(records[1].is_err());
+        assert_eq!(
+            records[2].as_ref().unwrap().header("WARC-Target-URI"),
+            Some("https://example.com/2")
+        );
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let mut out = vec![];
+        let mut writer = WarcWriter::new(&mut out, true);
+
+        writer
+            .write_record("warcinfo", Utc::now(), &[], b"software: seen\r\n")
+            .unwrap();
+        writer
+            .write_record(
+                "response",
+                Utc::now(),
+                &[("WARC-Target-URI", "https://example.com/")],
+                b"HTTP/1.1 200 OK\r\n\r\nHi",
+            )
+            .unwrap();
+
+        let records = WarcReader::new(std::io::Cursor::new(out))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[1].header("WARC-Target-URI"),
+            Some("https://example.com/")
+        );
+        assert_eq!(parse_response(&records[1].block).unwrap().body, b"Hi");
+    }
+}